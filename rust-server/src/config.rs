@@ -0,0 +1,188 @@
+use serde::Deserialize;
+use std::env;
+use std::sync::Arc;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("invalid environment configuration: {0}")]
+    Env(#[from] envy::Error),
+    #[error("missing OPENAI_API_KEY or LLM_API_KEY")]
+    MissingLlmApiKey,
+    #[error("DB_MIN_CONNECTIONS ({min}) must not exceed DB_MAX_CONNECTIONS ({max})")]
+    InvalidPoolSize { min: u32, max: u32 },
+    #[error("LLM_TEMPERATURE must be between 0.0 and 2.0, got {0}")]
+    InvalidLlmTemperature(f32),
+    #[error("LLM_MAX_TOKENS must be greater than 0")]
+    InvalidLlmMaxTokens,
+}
+
+fn default_host() -> String {
+    "0.0.0.0".to_string()
+}
+
+fn default_port() -> u16 {
+    3000
+}
+
+fn default_redis_url() -> String {
+    "redis://127.0.0.1:6379".to_string()
+}
+
+fn default_llm_base_url() -> String {
+    "https://api.openai.com/v1".to_string()
+}
+
+fn default_jwt_access_ttl_secs() -> i64 {
+    15 * 60
+}
+
+fn default_jwt_refresh_ttl_days() -> i64 {
+    30
+}
+
+fn default_run_migrations() -> bool {
+    false
+}
+
+fn default_db_max_connections() -> u32 {
+    20
+}
+
+fn default_db_min_connections() -> u32 {
+    5
+}
+
+fn default_db_require_ssl() -> bool {
+    true
+}
+
+fn default_llm_model() -> String {
+    "gpt-4o-mini".to_string()
+}
+
+fn default_llm_max_tokens() -> u32 {
+    500
+}
+
+fn default_llm_temperature() -> f32 {
+    0.3
+}
+
+/// Mirrors the environment variables `Config::from_env` accepts. Kept
+/// separate from `Config` so the `OPENAI_API_KEY`/`LLM_API_KEY` fallback
+/// (not expressible as a single envy field) can be resolved by hand.
+#[derive(Debug, Deserialize)]
+struct RawConfig {
+    database_url: String,
+    jwt_secret: String,
+    #[serde(default = "default_jwt_access_ttl_secs")]
+    jwt_access_ttl: i64,
+    #[serde(default = "default_jwt_refresh_ttl_days")]
+    jwt_refresh_ttl: i64,
+    #[serde(default = "default_host")]
+    host: String,
+    #[serde(default = "default_port")]
+    port: u16,
+    #[serde(default = "default_redis_url")]
+    redis_url: String,
+    #[serde(default = "default_llm_base_url")]
+    llm_base_url: String,
+    #[serde(default = "default_run_migrations")]
+    run_migrations: bool,
+    #[serde(default = "default_db_max_connections")]
+    db_max_connections: u32,
+    #[serde(default = "default_db_min_connections")]
+    db_min_connections: u32,
+    #[serde(default = "default_db_require_ssl")]
+    db_require_ssl: bool,
+    #[serde(default = "default_llm_model")]
+    llm_model: String,
+    #[serde(default = "default_llm_max_tokens")]
+    llm_max_tokens: u32,
+    #[serde(default = "default_llm_temperature")]
+    llm_temperature: f32,
+}
+
+/// Application configuration, parsed once at startup from the environment.
+/// Replaces the `env::var(...).unwrap_or_else(...)` calls that used to be
+/// scattered across `main`, the auth middleware, and the LLM client —
+/// required values like `jwt_secret` now fail fast at boot instead of
+/// silently falling back to an insecure default.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub database_url: String,
+    pub jwt_secret: String,
+    /// Access token lifetime, in seconds.
+    pub jwt_access_ttl: i64,
+    /// Refresh token lifetime, in days.
+    pub jwt_refresh_ttl: i64,
+    pub host: String,
+    pub port: u16,
+    pub redis_url: String,
+    pub llm_api_key: String,
+    pub llm_base_url: String,
+    /// Run pending migrations on boot. Off by default so deployments that
+    /// prefer applying schema changes out-of-band (via the `migrate` CLI
+    /// subcommand) don't race a rolling restart against them.
+    pub run_migrations: bool,
+    pub db_max_connections: u32,
+    pub db_min_connections: u32,
+    /// Require TLS on the database connection. Only turned off for local
+    /// development against a MySQL instance without a configured cert.
+    pub db_require_ssl: bool,
+    pub llm_model: String,
+    pub llm_max_tokens: u32,
+    pub llm_temperature: f32,
+}
+
+impl Config {
+    pub fn from_env() -> Result<Self, ConfigError> {
+        let raw: RawConfig = envy::from_env()?;
+
+        let llm_api_key = env::var("OPENAI_API_KEY")
+            .or_else(|_| env::var("LLM_API_KEY"))
+            .map_err(|_| ConfigError::MissingLlmApiKey)?;
+
+        if raw.db_min_connections > raw.db_max_connections {
+            return Err(ConfigError::InvalidPoolSize {
+                min: raw.db_min_connections,
+                max: raw.db_max_connections,
+            });
+        }
+
+        if raw.llm_temperature < 0.0 || raw.llm_temperature > 2.0 {
+            return Err(ConfigError::InvalidLlmTemperature(raw.llm_temperature));
+        }
+
+        if raw.llm_max_tokens == 0 {
+            return Err(ConfigError::InvalidLlmMaxTokens);
+        }
+
+        Ok(Self {
+            database_url: raw.database_url,
+            jwt_secret: raw.jwt_secret,
+            jwt_access_ttl: raw.jwt_access_ttl,
+            jwt_refresh_ttl: raw.jwt_refresh_ttl,
+            host: raw.host,
+            port: raw.port,
+            redis_url: raw.redis_url,
+            llm_api_key,
+            llm_base_url: raw.llm_base_url,
+            run_migrations: raw.run_migrations,
+            db_max_connections: raw.db_max_connections,
+            db_min_connections: raw.db_min_connections,
+            db_require_ssl: raw.db_require_ssl,
+            llm_model: raw.llm_model,
+            llm_max_tokens: raw.llm_max_tokens,
+            llm_temperature: raw.llm_temperature,
+        })
+    }
+}
+
+/// Shared, cheaply-cloneable application state handed to handlers via
+/// `web::Data`, alongside the DB pool and LLM client.
+#[derive(Debug, Clone)]
+pub struct AppState {
+    pub config: Arc<Config>,
+}
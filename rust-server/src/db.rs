@@ -1,21 +1,44 @@
 use sqlx::mysql::{MySqlPool, MySqlPoolOptions, MySqlConnectOptions, MySqlSslMode};
-use std::env;
 use std::time::Duration;
 use std::str::FromStr;
 
+use crate::config::Config;
+
 pub type DbPool = MySqlPool;
 
-pub async fn create_pool() -> Result<DbPool, sqlx::Error> {
-    let database_url = env::var("DATABASE_URL")
-        .expect("DATABASE_URL must be set");
+/// A pluggable Postgres/SQLite backend behind a `Database` trait was
+/// requested (see `requests.jsonl` chunk1-5) and is closed won't-do: the
+/// query layer spans most of this file (`protocols::rank_by_bm25` builds
+/// queries with `QueryBuilder::<sqlx::MySql>` against a `MATCH ... AGAINST`
+/// FULLTEXT index, `protocols::upsert_embedding` relies on `ON DUPLICATE
+/// KEY UPDATE`, the `create` inserts here read back `last_insert_id()` with no
+/// Postgres/SQLite equivalent, and every `#[derive(FromRow)]` model is
+/// bound to `MySqlRow`'s column-rename behavior), so a real abstraction
+/// means migrating all of that plus a second migration set — not a change
+/// to pool setup. `DbPool` stays a plain `MySqlPool` alias and
+/// `create_pool` rejects any non-`mysql://` URL at startup instead of
+/// scaffolding an unused multi-backend enum that implies otherwise.
+pub async fn create_pool(config: &Config) -> Result<DbPool, sqlx::Error> {
+    if config.database_url.split("://").next() != Some("mysql") {
+        return Err(sqlx::Error::Configuration(
+            format!(
+                "DATABASE_URL must be a mysql:// URL — got {}; a pluggable backend was requested and closed won't-do, see db::create_pool",
+                config.database_url
+            )
+            .into(),
+        ));
+    }
 
-    // Parse the connection options and enable SSL
-    let options = MySqlConnectOptions::from_str(&database_url)?
-        .ssl_mode(MySqlSslMode::Required);
+    let ssl_mode = if config.db_require_ssl {
+        MySqlSslMode::Required
+    } else {
+        MySqlSslMode::Preferred
+    };
+    let options = MySqlConnectOptions::from_str(&config.database_url)?.ssl_mode(ssl_mode);
 
     let pool = MySqlPoolOptions::new()
-        .max_connections(20)
-        .min_connections(5)
+        .max_connections(config.db_max_connections)
+        .min_connections(config.db_min_connections)
         .acquire_timeout(Duration::from_secs(30))
         .idle_timeout(Duration::from_secs(600))
         .max_lifetime(Duration::from_secs(1800))
@@ -26,19 +49,76 @@ pub async fn create_pool() -> Result<DbPool, sqlx::Error> {
     Ok(pool)
 }
 
+/// Embedded schema migrations, applied in order and recorded in
+/// `_sqlx_migrations`. Run automatically on boot when `RUN_MIGRATIONS` is
+/// set, or on demand via `cargo run -- migrate` / `migrate revert`.
+pub mod migrate {
+    use sqlx::migrate::{MigrateError, Migrator};
+
+    use super::DbPool;
+
+    static MIGRATOR: Migrator = sqlx::migrate!("migrations");
+
+    /// Apply every migration that hasn't run yet.
+    pub async fn run_pending(pool: &DbPool) -> Result<(), MigrateError> {
+        MIGRATOR.run(pool).await
+    }
+
+    /// Revert the single most recently applied migration.
+    pub async fn revert_last(pool: &DbPool) -> Result<(), MigrateError> {
+        let applied: Vec<i64> = sqlx::query_scalar(
+            "SELECT version FROM _sqlx_migrations WHERE success ORDER BY version DESC LIMIT 2",
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let target = applied.get(1).copied().unwrap_or(0);
+        MIGRATOR.undo(pool, target).await
+    }
+}
+
 // County queries
 pub mod counties {
     use super::*;
-    use crate::models::{County, CountyWithProtocolCount, StateWithCount};
+    use crate::models::{County, CountyWithProtocolCount, Cursor, StateWithCount};
 
     pub async fn get_all(pool: &DbPool) -> Result<Vec<County>, sqlx::Error> {
         sqlx::query_as::<_, County>(
-            "SELECT * FROM counties ORDER BY state, name"
+            "SELECT * FROM counties ORDER BY state, name, id"
         )
         .fetch_all(pool)
         .await
     }
 
+    /// Keyset-paginated county listing, preserving `get_all`'s alphabetical
+    /// `state, name` order. That order has no single SQL-orderable column
+    /// behind it, so (same as `protocols::search_page` does for BM25 scores)
+    /// the full list is fetched and sorted once and each row's position in
+    /// it becomes the cursor's `sort_key`; counties is a small reference
+    /// table, not a growing corpus, so fetching it whole per page is cheap.
+    /// Returns each county alongside its rank so callers can build the next
+    /// cursor from it.
+    pub async fn get_all_page(
+        pool: &DbPool,
+        limit: i32,
+        after: Option<Cursor>,
+    ) -> Result<Vec<(County, i64)>, sqlx::Error> {
+        let all = get_all(pool).await?;
+
+        let mut ranked: Vec<(County, i64)> = all
+            .into_iter()
+            .enumerate()
+            .map(|(rank, county)| (county, rank as i64))
+            .collect();
+
+        if let Some(cursor) = after {
+            ranked.retain(|(county, rank)| (*rank, county.id) > (cursor.sort_key, cursor.id));
+        }
+
+        ranked.truncate((limit.max(0) + 1) as usize);
+        Ok(ranked)
+    }
+
     pub async fn get_by_id(pool: &DbPool, id: i32) -> Result<Option<County>, sqlx::Error> {
         sqlx::query_as::<_, County>(
             "SELECT * FROM counties WHERE id = ?"
@@ -48,6 +128,23 @@ pub mod counties {
         .await
     }
 
+    /// Batch-fetch counties by id in a single query, e.g. to resolve county
+    /// metadata for a page of search results without an N+1 lookup loop.
+    pub async fn get_by_ids(pool: &DbPool, ids: &[i32]) -> Result<Vec<County>, sqlx::Error> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut qb = sqlx::QueryBuilder::<sqlx::MySql>::new("SELECT * FROM counties WHERE id IN (");
+        let mut separated = qb.separated(", ");
+        for id in ids {
+            separated.push_bind(id);
+        }
+        separated.push_unseparated(")");
+
+        qb.build_query_as::<County>().fetch_all(pool).await
+    }
+
     pub async fn get_by_state(pool: &DbPool, state: &str) -> Result<Vec<CountyWithProtocolCount>, sqlx::Error> {
         sqlx::query_as::<_, CountyWithProtocolCount>(
             r#"
@@ -85,106 +182,211 @@ pub mod counties {
 // Protocol queries
 pub mod protocols {
     use super::*;
-    use crate::models::{ProtocolChunk, ProtocolStats};
+    use crate::models::{Cursor, ProtocolChunk, ProtocolStats};
+    use crate::services::llm::{LlmClient, LlmError, EMBEDDING_MODEL};
+    use chrono::{DateTime, Utc};
+    use std::collections::HashMap;
+
+    const BM25_K1: f64 = 1.2;
+    const BM25_B: f64 = 0.75;
+
+    /// Lowercase and split on runs of non-alphanumeric characters, discarding
+    /// empty tokens. Shared by BM25 indexing and query tokenization so the
+    /// two sides of the comparison agree on what a "term" is.
+    fn tokenize(text: &str) -> Vec<String> {
+        text.to_lowercase()
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string())
+            .collect()
+    }
+
+    fn chunk_tokens(title: &str, section: Option<&str>, content: &str) -> Vec<String> {
+        let mut tokens = tokenize(title);
+        if let Some(section) = section {
+            tokens.extend(tokenize(section));
+        }
+        tokens.extend(tokenize(content));
+        tokens
+    }
 
+    /// Fixed-point scale applied to a BM25 score before it's stored in a
+    /// `Cursor`, which only carries an `i64`. Six decimal digits of
+    /// precision is far finer than BM25 scores ever need to differ by, so
+    /// this doesn't introduce ordering ties that wouldn't already exist.
+    const SCORE_SORT_SCALE: f64 = 1_000_000.0;
+
+    /// Map a BM25 score to the `Cursor::sort_key` that orders it, so
+    /// `search_page` and its callers (building the next page's cursor)
+    /// agree on the same mapping.
+    pub fn score_to_sort_key(score: f64) -> i64 {
+        (score * SCORE_SORT_SCALE).round() as i64
+    }
+
+    /// Fetch every chunk matching `query` in scope and score it with BM25,
+    /// fully sorted (ties broken by `id` descending so the ordering is
+    /// total and reproducible across calls). Shared by `search` (which
+    /// truncates to `limit`) and `search_page` (which keyset-paginates over
+    /// it) so both ranking entry points compute the identical score.
+    async fn rank_by_bm25(
+        pool: &DbPool,
+        query: &str,
+        state: Option<&str>,
+        county_id: Option<i32>,
+    ) -> Result<Vec<(ProtocolChunk, f64)>, sqlx::Error> {
+        let terms = tokenize(query);
+        if terms.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut candidates_qb = sqlx::QueryBuilder::<sqlx::MySql>::new(
+            "SELECT p.* FROM protocolChunks p JOIN counties c ON p.countyId = c.id WHERE 1 = 1"
+        );
+        if let Some(state) = state {
+            candidates_qb.push(" AND c.state = ").push_bind(state);
+        }
+        if let Some(cid) = county_id {
+            candidates_qb.push(" AND p.countyId = ").push_bind(cid);
+        }
+        candidates_qb
+            .push(" AND MATCH(p.protocolTitle, p.section, p.content) AGAINST(")
+            .push_bind(query)
+            .push(" IN NATURAL LANGUAGE MODE)");
+
+        let candidates = candidates_qb
+            .build_query_as::<ProtocolChunk>()
+            .fetch_all(pool)
+            .await?;
+
+        if candidates.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // IDF and avgdl are corpus statistics: computed over every chunk in
+        // the same state/county scope, not just the matched candidates.
+        let mut scope_qb = sqlx::QueryBuilder::<sqlx::MySql>::new(
+            "SELECT p.protocolTitle, p.section, p.content FROM protocolChunks p JOIN counties c ON p.countyId = c.id WHERE 1 = 1"
+        );
+        if let Some(state) = state {
+            scope_qb.push(" AND c.state = ").push_bind(state);
+        }
+        if let Some(cid) = county_id {
+            scope_qb.push(" AND p.countyId = ").push_bind(cid);
+        }
+
+        let scope_rows: Vec<(String, Option<String>, String)> =
+            scope_qb.build_query_as().fetch_all(pool).await?;
+
+        let scope_docs: Vec<Vec<String>> = scope_rows
+            .iter()
+            .map(|(title, section, content)| chunk_tokens(title, section.as_deref(), content))
+            .collect();
+
+        let doc_count = scope_docs.len() as f64;
+        let avgdl = if doc_count > 0.0 {
+            scope_docs.iter().map(|d| d.len() as f64).sum::<f64>() / doc_count
+        } else {
+            0.0
+        };
+
+        let mut doc_freq: HashMap<&str, usize> = HashMap::new();
+        for doc in &scope_docs {
+            let unique: std::collections::HashSet<&str> = doc.iter().map(String::as_str).collect();
+            for term in unique {
+                *doc_freq.entry(term).or_insert(0) += 1;
+            }
+        }
+
+        let idf: HashMap<&str, f64> = terms
+            .iter()
+            .map(|t| {
+                let n_t = doc_freq.get(t.as_str()).copied().unwrap_or(0) as f64;
+                let score = (1.0 + (doc_count - n_t + 0.5) / (n_t + 0.5)).ln();
+                (t.as_str(), score)
+            })
+            .collect();
+
+        let mut scored: Vec<(ProtocolChunk, f64)> = candidates
+            .into_iter()
+            .map(|chunk| {
+                let tokens = chunk_tokens(&chunk.protocol_title, chunk.section.as_deref(), &chunk.content);
+                let doc_len = tokens.len() as f64;
+
+                let mut tf: HashMap<&str, usize> = HashMap::new();
+                for token in &tokens {
+                    *tf.entry(token.as_str()).or_insert(0) += 1;
+                }
+
+                let score: f64 = terms
+                    .iter()
+                    .map(|t| {
+                        let term_freq = tf.get(t.as_str()).copied().unwrap_or(0) as f64;
+                        if term_freq == 0.0 {
+                            return 0.0;
+                        }
+                        let idf_t = idf.get(t.as_str()).copied().unwrap_or(0.0);
+                        idf_t * (term_freq * (BM25_K1 + 1.0))
+                            / (term_freq + BM25_K1 * (1.0 - BM25_B + BM25_B * (doc_len / avgdl.max(1.0))))
+                    })
+                    .sum();
+
+                (chunk, score)
+            })
+            .collect();
+
+        scored.sort_by(|a, b| {
+            b.1.partial_cmp(&a.1)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| b.0.id.cmp(&a.0.id))
+        });
+
+        Ok(scored)
+    }
+
+    /// BM25-ranked protocol search, replacing the old `LIKE` + title-priority
+    /// `CASE` ordering. A `FULLTEXT` index narrows the candidates to chunks
+    /// containing at least one query term; BM25 itself is then computed in
+    /// Rust over that scope's own corpus statistics (`N`, `avgdl`, per-term
+    /// `n_t`) rather than relying on MySQL's own relevance scoring, so the
+    /// score matches the textbook formula exactly.
     pub async fn search(
         pool: &DbPool,
         query: &str,
         state: Option<&str>,
         county_id: Option<i32>,
         limit: i32,
-    ) -> Result<Vec<ProtocolChunk>, sqlx::Error> {
-        let search_pattern = format!("%{}%", query);
-        
-        let sql = match (state, county_id) {
-            (Some(state), Some(cid)) => {
-                sqlx::query_as::<_, ProtocolChunk>(
-                    r#"
-                    SELECT p.* FROM protocolChunks p
-                    JOIN counties c ON p.countyId = c.id
-                    WHERE c.state = ? AND p.countyId = ?
-                    AND (p.protocolTitle LIKE ? OR p.content LIKE ? OR p.section LIKE ?)
-                    ORDER BY 
-                        CASE WHEN p.protocolTitle LIKE ? THEN 0 ELSE 1 END,
-                        p.protocolTitle
-                    LIMIT ?
-                    "#
-                )
-                .bind(state)
-                .bind(cid)
-                .bind(&search_pattern)
-                .bind(&search_pattern)
-                .bind(&search_pattern)
-                .bind(&search_pattern)
-                .bind(limit)
-                .fetch_all(pool)
-                .await
-            }
-            (Some(state), None) => {
-                sqlx::query_as::<_, ProtocolChunk>(
-                    r#"
-                    SELECT p.* FROM protocolChunks p
-                    JOIN counties c ON p.countyId = c.id
-                    WHERE c.state = ?
-                    AND (p.protocolTitle LIKE ? OR p.content LIKE ? OR p.section LIKE ?)
-                    ORDER BY 
-                        CASE WHEN p.protocolTitle LIKE ? THEN 0 ELSE 1 END,
-                        p.protocolTitle
-                    LIMIT ?
-                    "#
-                )
-                .bind(state)
-                .bind(&search_pattern)
-                .bind(&search_pattern)
-                .bind(&search_pattern)
-                .bind(&search_pattern)
-                .bind(limit)
-                .fetch_all(pool)
-                .await
-            }
-            (None, Some(cid)) => {
-                sqlx::query_as::<_, ProtocolChunk>(
-                    r#"
-                    SELECT p.* FROM protocolChunks p
-                    WHERE p.countyId = ?
-                    AND (p.protocolTitle LIKE ? OR p.content LIKE ? OR p.section LIKE ?)
-                    ORDER BY 
-                        CASE WHEN p.protocolTitle LIKE ? THEN 0 ELSE 1 END,
-                        p.protocolTitle
-                    LIMIT ?
-                    "#
-                )
-                .bind(cid)
-                .bind(&search_pattern)
-                .bind(&search_pattern)
-                .bind(&search_pattern)
-                .bind(&search_pattern)
-                .bind(limit)
-                .fetch_all(pool)
-                .await
-            }
-            (None, None) => {
-                sqlx::query_as::<_, ProtocolChunk>(
-                    r#"
-                    SELECT * FROM protocolChunks
-                    WHERE protocolTitle LIKE ? OR content LIKE ? OR section LIKE ?
-                    ORDER BY 
-                        CASE WHEN protocolTitle LIKE ? THEN 0 ELSE 1 END,
-                        protocolTitle
-                    LIMIT ?
-                    "#
-                )
-                .bind(&search_pattern)
-                .bind(&search_pattern)
-                .bind(&search_pattern)
-                .bind(&search_pattern)
-                .bind(limit)
-                .fetch_all(pool)
-                .await
-            }
-        };
-        
-        sql
+    ) -> Result<Vec<(ProtocolChunk, f64)>, sqlx::Error> {
+        let mut scored = rank_by_bm25(pool, query, state, county_id).await?;
+        scored.truncate(limit.max(0) as usize);
+        Ok(scored)
+    }
+
+    /// Keyset-paginated protocol search, ranked by the same BM25 score as
+    /// `search`. The full candidate set is already scored and sorted
+    /// in-memory (BM25 requires scope-wide corpus stats, so there's no way
+    /// to do this with a SQL `LIMIT`/`OFFSET`), so pagination keyset-filters
+    /// that in-memory list: rows are kept only if `(sort_key, id)` sorts
+    /// after the cursor under the same `(score DESC, id DESC)` order used
+    /// by `rank_by_bm25`, mirroring the `(sort_key, id) < (cursor)` pattern
+    /// the other `*_page` queries apply in SQL.
+    pub async fn search_page(
+        pool: &DbPool,
+        query: &str,
+        state: Option<&str>,
+        county_id: Option<i32>,
+        limit: i32,
+        after: Option<Cursor>,
+    ) -> Result<Vec<(ProtocolChunk, f64)>, sqlx::Error> {
+        let mut scored = rank_by_bm25(pool, query, state, county_id).await?;
+
+        if let Some(cursor) = after {
+            scored.retain(|(chunk, score)| {
+                (score_to_sort_key(*score), chunk.id) < (cursor.sort_key, cursor.id)
+            });
+        }
+
+        scored.truncate((limit.max(0) + 1) as usize);
+        Ok(scored)
     }
 
     pub async fn get_stats(pool: &DbPool) -> Result<ProtocolStats, sqlx::Error> {
@@ -214,6 +416,230 @@ pub mod protocols {
         .fetch_all(pool)
         .await
     }
+
+    /// Serialize an embedding vector as little-endian `f32` bytes for
+    /// storage in `protocolChunkEmbeddings.vector`.
+    pub fn encode_vector(vector: &[f32]) -> Vec<u8> {
+        vector.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    /// Inverse of `encode_vector`.
+    pub fn decode_vector(bytes: &[u8]) -> Vec<f32> {
+        bytes
+            .chunks_exact(4)
+            .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+            .collect()
+    }
+
+    fn l2_norm(vector: &[f32]) -> f64 {
+        vector.iter().map(|v| (*v as f64) * (*v as f64)).sum::<f64>().sqrt()
+    }
+
+    fn dot(a: &[f32], b: &[f32]) -> f64 {
+        a.iter().zip(b).map(|(x, y)| (*x as f64) * (*y as f64)).sum()
+    }
+
+    /// Chunks that don't yet have a stored embedding, oldest first. Paged
+    /// through by the `embed-backfill` CLI subcommand.
+    pub async fn get_unembedded(pool: &DbPool, limit: i32) -> Result<Vec<ProtocolChunk>, sqlx::Error> {
+        sqlx::query_as::<_, ProtocolChunk>(
+            r#"
+            SELECT p.* FROM protocolChunks p
+            LEFT JOIN protocolChunkEmbeddings e ON e.protocolId = p.id
+            WHERE e.id IS NULL
+            ORDER BY p.id
+            LIMIT ?
+            "#
+        )
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Insert or replace the stored embedding for a protocol chunk,
+    /// precomputing and caching its L2 norm alongside the vector.
+    pub async fn upsert_embedding(
+        pool: &DbPool,
+        protocol_id: i32,
+        vector: &[f32],
+        model: &str,
+    ) -> Result<(), sqlx::Error> {
+        let bytes = encode_vector(vector);
+        let norm = l2_norm(vector);
+        let dims = vector.len() as i32;
+
+        sqlx::query(
+            r#"
+            INSERT INTO protocolChunkEmbeddings (protocolId, vector, model, dims, norm)
+            VALUES (?, ?, ?, ?, ?)
+            ON DUPLICATE KEY UPDATE vector = VALUES(vector), model = VALUES(model),
+                dims = VALUES(dims), norm = VALUES(norm)
+            "#
+        )
+        .bind(protocol_id)
+        .bind(bytes)
+        .bind(model)
+        .bind(dims)
+        .bind(norm)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Embed and store one batch of chunks that don't have an embedding
+    /// yet. Returns the number of chunks embedded, so the caller (the
+    /// `embed-backfill` CLI subcommand) knows to keep looping until it
+    /// gets back `0`.
+    pub async fn backfill_embeddings(
+        pool: &DbPool,
+        llm: &LlmClient,
+        batch_size: i32,
+    ) -> Result<usize, LlmError> {
+        let chunks = get_unembedded(pool, batch_size).await?;
+        if chunks.is_empty() {
+            return Ok(0);
+        }
+
+        let texts: Vec<String> = chunks.iter().map(|c| c.content.clone()).collect();
+        let vectors = llm.embed(&texts).await?;
+
+        for (chunk, vector) in chunks.iter().zip(vectors.iter()) {
+            upsert_embedding(pool, chunk.id, vector, EMBEDDING_MODEL).await?;
+        }
+
+        Ok(chunks.len())
+    }
+
+    /// A protocol chunk row joined with its stored embedding, used
+    /// internally by `rank_by_cosine`.
+    #[derive(Debug, sqlx::FromRow)]
+    struct EmbeddedProtocolChunk {
+        id: i32,
+        #[sqlx(rename = "countyId")]
+        county_id: i32,
+        #[sqlx(rename = "protocolNumber")]
+        protocol_number: String,
+        #[sqlx(rename = "protocolTitle")]
+        protocol_title: String,
+        section: Option<String>,
+        content: String,
+        #[sqlx(rename = "sourcePdfUrl")]
+        source_pdf_url: Option<String>,
+        #[sqlx(rename = "protocolEffectiveDate")]
+        protocol_effective_date: Option<String>,
+        #[sqlx(rename = "lastVerifiedAt")]
+        last_verified_at: Option<DateTime<Utc>>,
+        #[sqlx(rename = "protocolYear")]
+        protocol_year: Option<i32>,
+        #[sqlx(rename = "createdAt")]
+        created_at: DateTime<Utc>,
+        vector: Vec<u8>,
+        norm: f64,
+    }
+
+    impl EmbeddedProtocolChunk {
+        fn into_chunk(self) -> ProtocolChunk {
+            ProtocolChunk {
+                id: self.id,
+                county_id: self.county_id,
+                protocol_number: self.protocol_number,
+                protocol_title: self.protocol_title,
+                section: self.section,
+                content: self.content,
+                source_pdf_url: self.source_pdf_url,
+                protocol_effective_date: self.protocol_effective_date,
+                last_verified_at: self.last_verified_at,
+                protocol_year: self.protocol_year,
+                created_at: self.created_at,
+            }
+        }
+    }
+
+    /// Fetch every chunk in scope that has a stored embedding and score it
+    /// by cosine similarity to `query_vector`, fully sorted (ties broken by
+    /// `id` descending, same convention as `rank_by_bm25`). Returns an empty
+    /// vec, not an error, when no chunk in scope has been embedded yet, so
+    /// callers can tell "no embeddings" apart from "no matches" and fall
+    /// back to BM25.
+    async fn rank_by_cosine(
+        pool: &DbPool,
+        query_vector: &[f32],
+        state: Option<&str>,
+        county_id: Option<i32>,
+    ) -> Result<Vec<(ProtocolChunk, f64)>, sqlx::Error> {
+        let mut qb = sqlx::QueryBuilder::<sqlx::MySql>::new(
+            "SELECT p.*, e.vector, e.norm FROM protocolChunks p \
+             JOIN protocolChunkEmbeddings e ON e.protocolId = p.id \
+             JOIN counties c ON p.countyId = c.id WHERE 1 = 1"
+        );
+
+        if let Some(state) = state {
+            qb.push(" AND c.state = ").push_bind(state);
+        }
+        if let Some(cid) = county_id {
+            qb.push(" AND p.countyId = ").push_bind(cid);
+        }
+
+        let candidates = qb
+            .build_query_as::<EmbeddedProtocolChunk>()
+            .fetch_all(pool)
+            .await?;
+
+        let query_norm = l2_norm(query_vector);
+
+        let mut scored: Vec<(ProtocolChunk, f64)> = candidates
+            .into_iter()
+            .map(|row| {
+                let vector = decode_vector(&row.vector);
+                let similarity = if query_norm > 0.0 && row.norm > 0.0 {
+                    dot(&vector, query_vector) / (row.norm * query_norm)
+                } else {
+                    0.0
+                };
+                (row.into_chunk(), similarity)
+            })
+            .collect();
+
+        scored.sort_by(|a, b| {
+            b.1.partial_cmp(&a.1)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| b.0.id.cmp(&a.0.id))
+        });
+
+        Ok(scored)
+    }
+
+    /// Keyset-paginated cosine-similarity search, used by the live
+    /// `/api/search` endpoint: the scored list is fully materialized in
+    /// memory, then filtered to rows that sort after `after` under the same
+    /// `(score DESC, id DESC)` order `search_page` uses for its BM25 keyset
+    /// filter. Returns `Ok(None)` when no chunk in scope has been embedded
+    /// yet, so the caller falls back to `search_page` explicitly rather than
+    /// this function silently returning BM25 scores labeled as cosine ones.
+    pub async fn semantic_search_page(
+        pool: &DbPool,
+        query_vector: &[f32],
+        state: Option<&str>,
+        county_id: Option<i32>,
+        limit: i32,
+        after: Option<Cursor>,
+    ) -> Result<Option<Vec<(ProtocolChunk, f64)>>, sqlx::Error> {
+        let mut scored = rank_by_cosine(pool, query_vector, state, county_id).await?;
+
+        if scored.is_empty() {
+            return Ok(None);
+        }
+
+        if let Some(cursor) = after {
+            scored.retain(|(chunk, score)| {
+                (score_to_sort_key(*score), chunk.id) < (cursor.sort_key, cursor.id)
+            });
+        }
+
+        scored.truncate((limit.max(0) + 1) as usize);
+        Ok(Some(scored))
+    }
 }
 
 // User queries
@@ -267,31 +693,235 @@ pub mod users {
             .await?;
         Ok(())
     }
+
+    /// Bump `tokenVersion`, invalidating every access token already issued
+    /// for this user (`AuthenticatedUser::from_request` rejects any token
+    /// whose `ver` claim doesn't match the stored version). Called on a
+    /// "log out everywhere" request alongside
+    /// `refresh_tokens::revoke_all_for_user`, so neither the holder's
+    /// current access token nor their other refresh tokens keep working.
+    pub async fn bump_token_version(pool: &DbPool, user_id: i32) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE users SET tokenVersion = tokenVersion + 1 WHERE id = ?")
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Outcome of `check_and_consume_quota`: the tier's daily ceiling, how
+    /// many queries remain for the rest of today, and when the count next
+    /// resets (midnight UTC, as a Unix timestamp).
+    #[derive(Debug, Clone, Copy)]
+    pub struct QuotaStatus {
+        pub limit: i32,
+        pub remaining: i32,
+        pub reset_at: i64,
+    }
+
+    /// Daily query ceiling for a tier. Enforced here directly against
+    /// `users.queryCountToday`, independent of (and in addition to) the
+    /// Redis-backed `RateLimiter` daily/burst windows — this is the
+    /// last-line guard that still holds even if Redis is unavailable.
+    fn tier_daily_limit(tier: &str) -> i32 {
+        match tier {
+            "pro" | "enterprise" => 1000,
+            _ => 5,
+        }
+    }
+
+    fn next_midnight_utc() -> i64 {
+        let now = chrono::Utc::now();
+        let tomorrow = now.date_naive() + chrono::Days::new(1);
+        tomorrow.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp()
+    }
+
+    /// Atomically check a user's daily query quota and consume one unit of
+    /// it, rejecting the request before any LLM call is made if they're
+    /// already at the limit. `today` resets `queryCountToday` to 1 the first
+    /// time it differs from the stored `lastQueryDate`, same as
+    /// `increment_query_count`.
+    /// Returns `Ok(None)` if the user is already at their daily limit (the
+    /// request is rejected, nothing is consumed) and `Ok(Some(status))` once
+    /// the increment has been committed.
+    pub async fn check_and_consume_quota(
+        pool: &DbPool,
+        user_id: i32,
+        today: &str,
+    ) -> Result<Option<QuotaStatus>, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        let row: Option<(i32, Option<String>, String)> = sqlx::query_as(
+            "SELECT queryCountToday, lastQueryDate, tier FROM users WHERE id = ? FOR UPDATE"
+        )
+        .bind(user_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let (count_today, last_query_date, tier) = row.ok_or(sqlx::Error::RowNotFound)?;
+        let limit = tier_daily_limit(&tier);
+        let reset_at = next_midnight_utc();
+
+        let count_before_this_request = if last_query_date.as_deref() == Some(today) {
+            count_today
+        } else {
+            0
+        };
+
+        if count_before_this_request >= limit {
+            tx.commit().await?;
+            return Ok(None);
+        }
+
+        let new_count = count_before_this_request + 1;
+
+        sqlx::query("UPDATE users SET queryCountToday = ?, lastQueryDate = ? WHERE id = ?")
+            .bind(new_count)
+            .bind(today)
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(Some(QuotaStatus {
+            limit,
+            remaining: (limit - new_count).max(0),
+            reset_at,
+        }))
+    }
 }
 
-// Query history
-pub mod query_logs {
+// Refresh tokens
+pub mod refresh_tokens {
     use super::*;
-    use crate::models::{QueryLog, QueryHistoryItem};
+    use crate::models::RefreshToken;
+    use chrono::{DateTime, Utc};
 
     pub async fn create(
         pool: &DbPool,
         user_id: i32,
-        county_id: i32,
-        query_text: &str,
-        response_text: Option<&str>,
-        protocol_refs: Option<&str>,
+        token_hash: &str,
+        expires_at: DateTime<Utc>,
     ) -> Result<i32, sqlx::Error> {
+        let result = sqlx::query(
+            "INSERT INTO refresh_tokens (userId, tokenHash, expiresAt) VALUES (?, ?, ?)"
+        )
+        .bind(user_id)
+        .bind(token_hash)
+        .bind(expires_at)
+        .execute(pool)
+        .await?;
+
+        Ok(result.last_insert_id() as i32)
+    }
+
+    /// Look up a non-revoked, non-expired refresh token by its hash.
+    pub async fn get_valid_by_hash(pool: &DbPool, token_hash: &str) -> Result<Option<RefreshToken>, sqlx::Error> {
+        sqlx::query_as::<_, RefreshToken>(
+            r#"
+            SELECT * FROM refresh_tokens
+            WHERE tokenHash = ? AND revokedAt IS NULL AND expiresAt > NOW()
+            "#
+        )
+        .bind(token_hash)
+        .fetch_optional(pool)
+        .await
+    }
+
+    /// Validate, revoke, and replace a refresh token as one atomic unit: a
+    /// transaction with a `FOR UPDATE` row lock on the token being rotated,
+    /// the same pattern `users::check_and_consume_quota` uses for its own
+    /// read-then-write race. Without this, two requests racing on the same
+    /// refresh token could both pass `get_valid_by_hash` before either call
+    /// to `revoke` lands, and both would successfully mint a replacement
+    /// token from a refresh token that should only be usable once.
+    ///
+    /// Returns the rotated-out token's `userId` so the caller can issue new
+    /// access/refresh tokens for the right user, or `Ok(None)` if the hash
+    /// doesn't match a currently valid token.
+    pub async fn rotate(
+        pool: &DbPool,
+        token_hash: &str,
+        new_hash: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<Option<i32>, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        let stored = sqlx::query_as::<_, RefreshToken>(
+            r#"
+            SELECT * FROM refresh_tokens
+            WHERE tokenHash = ? AND revokedAt IS NULL AND expiresAt > NOW()
+            FOR UPDATE
+            "#
+        )
+        .bind(token_hash)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(stored) = stored else {
+            tx.commit().await?;
+            return Ok(None);
+        };
+
+        sqlx::query("UPDATE refresh_tokens SET revokedAt = NOW() WHERE id = ?")
+            .bind(stored.id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query(
+            "INSERT INTO refresh_tokens (userId, tokenHash, expiresAt) VALUES (?, ?, ?)"
+        )
+        .bind(stored.user_id)
+        .bind(new_hash)
+        .bind(expires_at)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(Some(stored.user_id))
+    }
+
+    pub async fn revoke(pool: &DbPool, id: i32) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE refresh_tokens SET revokedAt = NOW() WHERE id = ?")
+            .bind(id)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Revoke every outstanding refresh token for a user, e.g. on password
+    /// change or a "log out everywhere" request.
+    pub async fn revoke_all_for_user(pool: &DbPool, user_id: i32) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE refresh_tokens SET revokedAt = NOW() WHERE userId = ? AND revokedAt IS NULL")
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+}
+
+// Query history
+pub mod query_logs {
+    use super::*;
+    use crate::models::{CreateQueryLog, Cursor, QueryLog, QueryHistoryItem};
+
+    pub async fn create(pool: &DbPool, log: &CreateQueryLog) -> Result<i32, sqlx::Error> {
+        let protocol_refs = log
+            .protocol_refs
+            .as_ref()
+            .and_then(|refs| serde_json::to_string(refs).ok());
+
         let result = sqlx::query(
             r#"
             INSERT INTO queries (userId, countyId, queryText, responseText, protocolRefs)
             VALUES (?, ?, ?, ?, ?)
             "#
         )
-        .bind(user_id)
-        .bind(county_id)
-        .bind(query_text)
-        .bind(response_text)
+        .bind(log.user_id)
+        .bind(log.county_id)
+        .bind(&log.query_text)
+        .bind(&log.response_text)
         .bind(protocol_refs)
         .execute(pool)
         .await?;
@@ -299,21 +929,186 @@ pub mod query_logs {
         Ok(result.last_insert_id() as i32)
     }
 
-    pub async fn get_user_history(pool: &DbPool, user_id: i32, limit: i32) -> Result<Vec<QueryHistoryItem>, sqlx::Error> {
-        sqlx::query_as::<_, QueryHistoryItem>(
+    /// Keyset-paginated query history, ordered newest first.
+    pub async fn get_user_history_page(
+        pool: &DbPool,
+        user_id: i32,
+        limit: i32,
+        after: Option<Cursor>,
+    ) -> Result<Vec<QueryHistoryItem>, sqlx::Error> {
+        match after {
+            Some(cursor) => {
+                sqlx::query_as::<_, QueryHistoryItem>(
+                    r#"
+                    SELECT q.id, q.queryText as query_text, q.responseText as response_text,
+                           c.name as county_name, c.state, q.createdAt as created_at
+                    FROM queries q
+                    JOIN counties c ON q.countyId = c.id
+                    WHERE q.userId = ? AND (q.createdAt, q.id) < (FROM_UNIXTIME(?), ?)
+                    ORDER BY q.createdAt DESC, q.id DESC
+                    LIMIT ?
+                    "#
+                )
+                .bind(user_id)
+                .bind(cursor.sort_key)
+                .bind(cursor.id)
+                .bind(limit + 1)
+                .fetch_all(pool)
+                .await
+            }
+            None => {
+                sqlx::query_as::<_, QueryHistoryItem>(
+                    r#"
+                    SELECT q.id, q.queryText as query_text, q.responseText as response_text,
+                           c.name as county_name, c.state, q.createdAt as created_at
+                    FROM queries q
+                    JOIN counties c ON q.countyId = c.id
+                    WHERE q.userId = ?
+                    ORDER BY q.createdAt DESC, q.id DESC
+                    LIMIT ?
+                    "#
+                )
+                .bind(user_id)
+                .bind(limit + 1)
+                .fetch_all(pool)
+                .await
+            }
+        }
+    }
+}
+
+// Feedback queries
+pub mod feedback {
+    use super::*;
+    use crate::models::{CreateFeedback, Cursor, Feedback, FeedbackCategory, FeedbackStatus};
+
+    /// SQL `CASE` mirroring `FeedbackStatus`'s declaration order (pending
+    /// first, dismissed last), used to drive `list_for_admin`'s ordering and
+    /// keyset comparison.
+    const STATUS_PRIORITY_CASE: &str =
+        "CASE status WHEN 'pending' THEN 0 WHEN 'reviewed' THEN 1 WHEN 'resolved' THEN 2 WHEN 'dismissed' THEN 3 ELSE 4 END";
+
+    /// The same ordering as `STATUS_PRIORITY_CASE`, computed in Rust so a
+    /// caller building a `Cursor` for `list_for_admin` (e.g. to pass into
+    /// `Page::from_rows`) agrees with the SQL on a row's sort position.
+    pub fn status_priority(status: &str) -> i64 {
+        match status {
+            "pending" => 0,
+            "reviewed" => 1,
+            "resolved" => 2,
+            "dismissed" => 3,
+            _ => 4,
+        }
+    }
+
+    /// Confirm `value` deserializes as one of `T`'s variants before it's
+    /// written to a plain `VARCHAR` column, so `category`/`status` never
+    /// drift from the `FeedbackCategory`/`FeedbackStatus` enums.
+    fn validate<T: serde::de::DeserializeOwned>(value: &str, field: &str) -> Result<(), sqlx::Error> {
+        serde_json::from_value::<T>(serde_json::Value::String(value.to_string()))
+            .map(|_| ())
+            .map_err(|_| sqlx::Error::Configuration(format!("invalid {field}: {value:?}").into()))
+    }
+
+    pub async fn create(pool: &DbPool, feedback: &CreateFeedback) -> Result<i32, sqlx::Error> {
+        validate::<FeedbackCategory>(&feedback.category, "category")?;
+
+        let result = sqlx::query(
             r#"
-            SELECT q.id, q.queryText as query_text, q.responseText as response_text,
-                   c.name as county_name, c.state, q.createdAt as created_at
-            FROM queries q
-            JOIN counties c ON q.countyId = c.id
-            WHERE q.userId = ?
-            ORDER BY q.createdAt DESC
-            LIMIT ?
+            INSERT INTO feedback (userId, category, protocolRef, countyId, subject, message)
+            VALUES (?, ?, ?, ?, ?, ?)
             "#
         )
-        .bind(user_id)
-        .bind(limit)
+        .bind(feedback.user_id)
+        .bind(&feedback.category)
+        .bind(&feedback.protocol_ref)
+        .bind(feedback.county_id)
+        .bind(&feedback.subject)
+        .bind(&feedback.message)
+        .execute(pool)
+        .await?;
+
+        Ok(result.last_insert_id() as i32)
+    }
+
+    pub async fn get_by_status(pool: &DbPool, status: &str) -> Result<Vec<Feedback>, sqlx::Error> {
+        validate::<FeedbackStatus>(status, "status")?;
+
+        sqlx::query_as::<_, Feedback>(
+            "SELECT * FROM feedback WHERE status = ? ORDER BY id DESC"
+        )
+        .bind(status)
         .fetch_all(pool)
         .await
     }
+
+    pub async fn get_for_county(pool: &DbPool, county_id: i32) -> Result<Vec<Feedback>, sqlx::Error> {
+        sqlx::query_as::<_, Feedback>(
+            "SELECT * FROM feedback WHERE countyId = ? ORDER BY id DESC"
+        )
+        .bind(county_id)
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Transition a feedback item's status, optionally attaching admin
+    /// notes. `admin_notes: None` leaves any previously recorded notes
+    /// untouched rather than clearing them.
+    pub async fn update_status(
+        pool: &DbPool,
+        id: i32,
+        status: &str,
+        admin_notes: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        validate::<FeedbackStatus>(status, "status")?;
+
+        sqlx::query(
+            "UPDATE feedback SET status = ?, adminNotes = COALESCE(?, adminNotes), updatedAt = NOW() WHERE id = ?"
+        )
+        .bind(status)
+        .bind(admin_notes)
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Keyset-paginated feedback listing for the admin triage queue, ordered
+    /// by status (pending first, dismissed last) and then by recency within
+    /// each status.
+    pub async fn list_for_admin(
+        pool: &DbPool,
+        limit: i32,
+        after: Option<Cursor>,
+    ) -> Result<Vec<Feedback>, sqlx::Error> {
+        match after {
+            Some(cursor) => {
+                sqlx::query_as::<_, Feedback>(&format!(
+                    r#"
+                    SELECT * FROM feedback
+                    WHERE ({priority}) > ? OR (({priority}) = ? AND id < ?)
+                    ORDER BY ({priority}) ASC, id DESC
+                    LIMIT ?
+                    "#,
+                    priority = STATUS_PRIORITY_CASE
+                ))
+                .bind(cursor.sort_key)
+                .bind(cursor.sort_key)
+                .bind(cursor.id)
+                .bind(limit + 1)
+                .fetch_all(pool)
+                .await
+            }
+            None => {
+                sqlx::query_as::<_, Feedback>(&format!(
+                    "SELECT * FROM feedback ORDER BY ({priority}) ASC, id DESC LIMIT ?",
+                    priority = STATUS_PRIORITY_CASE
+                ))
+                .bind(limit + 1)
+                .fetch_all(pool)
+                .await
+            }
+        }
+    }
 }
@@ -0,0 +1,125 @@
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use serde::Serialize;
+use std::fmt;
+
+use crate::services::rate_limit::RateLimitError;
+
+/// Crate-wide error type. Every handler and middleware function returns
+/// `Result<_, ApiError>` so `?` replaces hand-rolled `map_err` blocks, and
+/// the JSON error body/status code/logging all live in one place.
+#[derive(Debug)]
+pub enum ApiError {
+    Unauthorized(String),
+    Forbidden(String),
+    RateLimited(String),
+    NotFound(String),
+    Database(sqlx::Error),
+    Llm(String),
+    Validation(String),
+    Internal(String),
+}
+
+#[derive(Debug, Serialize)]
+struct ErrorBody {
+    error: ErrorDetail,
+}
+
+#[derive(Debug, Serialize)]
+struct ErrorDetail {
+    code: &'static str,
+    message: String,
+}
+
+impl ApiError {
+    fn code(&self) -> &'static str {
+        match self {
+            Self::Unauthorized(_) => "unauthorized",
+            Self::Forbidden(_) => "forbidden",
+            Self::RateLimited(_) => "rate_limited",
+            Self::NotFound(_) => "not_found",
+            Self::Database(_) => "database_error",
+            Self::Llm(_) => "llm_error",
+            Self::Validation(_) => "validation_error",
+            Self::Internal(_) => "internal_error",
+        }
+    }
+
+    /// The message sent to clients. Internal details (e.g. the underlying
+    /// `sqlx::Error`) are logged in `error_response` but never exposed.
+    fn message(&self) -> String {
+        match self {
+            Self::Unauthorized(m) => m.clone(),
+            Self::Forbidden(m) => m.clone(),
+            Self::RateLimited(m) => m.clone(),
+            Self::NotFound(m) => m.clone(),
+            Self::Database(_) => "A database error occurred".to_string(),
+            Self::Llm(m) => m.clone(),
+            Self::Validation(m) => m.clone(),
+            Self::Internal(_) => "An internal error occurred".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            Self::Forbidden(_) => StatusCode::FORBIDDEN,
+            Self::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::Database(_) | Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::Llm(_) => StatusCode::BAD_GATEWAY,
+            Self::Validation(_) => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        match self {
+            Self::Database(e) => tracing::error!("Database error: {}", e),
+            Self::Internal(m) => tracing::error!("Internal error: {}", m),
+            Self::Llm(m) => tracing::warn!("LLM error: {}", m),
+            _ => {}
+        }
+
+        HttpResponse::build(self.status_code()).json(ErrorBody {
+            error: ErrorDetail {
+                code: self.code(),
+                message: self.message(),
+            },
+        })
+    }
+}
+
+impl From<sqlx::Error> for ApiError {
+    fn from(e: sqlx::Error) -> Self {
+        Self::Database(e)
+    }
+}
+
+impl From<jsonwebtoken::errors::Error> for ApiError {
+    fn from(e: jsonwebtoken::errors::Error) -> Self {
+        Self::Unauthorized(format!("Invalid token: {}", e))
+    }
+}
+
+impl From<RateLimitError> for ApiError {
+    fn from(e: RateLimitError) -> Self {
+        match e {
+            RateLimitError::DailyLimitExceeded => Self::Forbidden(
+                "Daily query limit reached. Upgrade to Pro for a higher daily limit.".to_string(),
+            ),
+            RateLimitError::BurstLimitExceeded => {
+                Self::RateLimited("Too many requests, please slow down.".to_string())
+            }
+            RateLimitError::Redis(e) => {
+                Self::Internal(format!("rate limiter unavailable: {}", e))
+            }
+        }
+    }
+}
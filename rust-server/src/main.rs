@@ -1,13 +1,21 @@
+mod config;
 mod db;
+mod error;
 mod middleware;
 mod models;
+mod openapi;
 mod routes;
 mod services;
 
 use actix_cors::Cors;
 use actix_web::{web, App, HttpServer};
 use std::env;
+use std::sync::Arc;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
+
+use config::{AppState, Config};
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
@@ -26,21 +34,70 @@ async fn main() -> std::io::Result<()> {
 
     tracing::info!("Starting Protocol Guide Rust Server");
 
+    // Load and validate configuration once at startup; fail fast on
+    // misconfiguration instead of falling back to insecure defaults.
+    let config = Arc::new(Config::from_env().expect("Failed to load configuration"));
+    let app_state = AppState { config: config.clone() };
+
     // Create database pool
-    let pool = db::create_pool()
+    let pool = db::create_pool(&config)
         .await
         .expect("Failed to create database pool");
 
+    // `cargo run -- migrate [revert]`: apply (or roll back) schema
+    // migrations and exit, without starting the HTTP server.
+    let cli_args: Vec<String> = env::args().collect();
+    if cli_args.get(1).map(String::as_str) == Some("migrate") {
+        if cli_args.get(2).map(String::as_str) == Some("revert") {
+            db::migrate::revert_last(&pool)
+                .await
+                .expect("Failed to revert migration");
+            tracing::info!("Reverted latest migration");
+        } else {
+            db::migrate::run_pending(&pool)
+                .await
+                .expect("Failed to run migrations");
+            tracing::info!("Migrations applied");
+        }
+        return Ok(());
+    }
+
+    if config.run_migrations {
+        db::migrate::run_pending(&pool)
+            .await
+            .expect("Failed to run migrations");
+    }
+
     // Create LLM client
-    let llm_client = services::llm::LlmClient::new()
-        .expect("Failed to create LLM client");
+    let llm_client = services::llm::LlmClient::new(&config);
+
+    // `cargo run -- embed-backfill [batch_size]`: embed every protocol
+    // chunk that doesn't have a stored embedding yet, then exit.
+    if cli_args.get(1).map(String::as_str) == Some("embed-backfill") {
+        let batch_size: i32 = cli_args
+            .get(2)
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(50);
+
+        loop {
+            let embedded = db::protocols::backfill_embeddings(&pool, &llm_client, batch_size)
+                .await
+                .expect("Failed to backfill embeddings");
+            if embedded == 0 {
+                break;
+            }
+            tracing::info!("Embedded {} protocol chunks", embedded);
+        }
+        return Ok(());
+    }
 
-    // Get server configuration
-    let host = env::var("HOST").unwrap_or_else(|_| "0.0.0.0".to_string());
-    let port: u16 = env::var("PORT")
-        .unwrap_or_else(|_| "3000".to_string())
-        .parse()
-        .expect("PORT must be a number");
+    // Create rate limiter
+    let rate_limiter = services::rate_limit::RateLimiter::new(&config)
+        .await
+        .expect("Failed to create rate limiter");
+
+    let host = config.host.clone();
+    let port = config.port;
 
     tracing::info!("Server listening on {}:{}", host, port);
 
@@ -58,13 +115,28 @@ async fn main() -> std::io::Result<()> {
             .wrap(actix_web::middleware::Logger::default())
             .app_data(web::Data::new(pool.clone()))
             .app_data(web::Data::new(llm_client.clone()))
+            .app_data(web::Data::new(rate_limiter.clone()))
+            .app_data(web::Data::new(app_state.clone()))
+            // API docs: Swagger UI + the generated OpenAPI document it reads from
+            .service(
+                SwaggerUi::new("/swagger-ui/{_:.*}")
+                    .url("/api-docs/openapi.json", openapi::ApiDoc::openapi()),
+            )
             // Health routes (no prefix)
             .route("/health", web::get().to(routes::health::health))
             .route("/ready", web::get().to(routes::health::ready))
+            // Auth routes
+            .service(
+                web::scope("/api/auth")
+                    .route("/refresh", web::post().to(routes::auth::refresh))
+                    .route("/logout", web::post().to(routes::auth::logout))
+                    .route("/logout-all", web::post().to(routes::auth::logout_all))
+            )
             // Search routes
             .service(
                 web::scope("/api/search")
                     .route("", web::get().to(routes::search::semantic_search))
+                    .route("/stream", web::get().to(routes::search::stream_answer))
                     .route("/stats", web::get().to(routes::search::get_stats))
                     .route("/county/{id}", web::get().to(routes::search::get_by_county))
             )
@@ -1,14 +1,25 @@
-use actix_web::{dev::ServiceRequest, Error, HttpMessage};
 use actix_web::web;
 use jsonwebtoken::{decode, DecodingKey, Validation, Algorithm};
+use rand::RngCore;
 use serde::{Deserialize, Serialize};
-use std::env;
+use sha2::{Digest, Sha256};
 
+use crate::config::{AppState, Config};
 use crate::db::{self, DbPool};
+use crate::error::ApiError;
+use crate::services::rate_limit::{RateLimitError, RateLimitStatus, RateLimiter};
+
+const ACCESS_TOKEN_TYPE: &str = "access";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
-    pub sub: String,  // openId
+    pub sub: String, // openId
+    /// Distinguishes an access token from a refresh token if one is ever
+    /// (mistakenly) presented as a bearer token.
+    pub typ: String,
+    /// Mirrors `users.tokenVersion`; bumping it server-side invalidates
+    /// every access token issued for the user, without a revocation list.
+    pub ver: i32,
     pub exp: usize,
     pub iat: usize,
 }
@@ -19,96 +30,144 @@ pub struct AuthenticatedUser {
     pub open_id: String,
     pub tier: String,
     pub query_count_today: i32,
+    pub selected_county_id: Option<i32>,
 }
 
 impl actix_web::FromRequest for AuthenticatedUser {
-    type Error = actix_web::Error;
+    type Error = ApiError;
     type Future = std::pin::Pin<Box<dyn std::future::Future<Output = Result<Self, Self::Error>>>>;
 
     fn from_request(req: &actix_web::HttpRequest, _payload: &mut actix_web::dev::Payload) -> Self::Future {
         let req = req.clone();
-        
+
         Box::pin(async move {
             // Get authorization header
             let auth_header = req
                 .headers()
                 .get("Authorization")
                 .and_then(|h| h.to_str().ok())
-                .ok_or_else(|| actix_web::error::ErrorUnauthorized("Missing authorization header"))?;
+                .ok_or_else(|| ApiError::Unauthorized("Missing authorization header".to_string()))?;
 
             // Extract Bearer token
             let token = auth_header
                 .strip_prefix("Bearer ")
-                .ok_or_else(|| actix_web::error::ErrorUnauthorized("Invalid authorization format"))?;
+                .ok_or_else(|| ApiError::Unauthorized("Invalid authorization format".to_string()))?;
+
+            // Get application state (holds the JWT secret) and the database pool
+            let state = req
+                .app_data::<web::Data<AppState>>()
+                .ok_or_else(|| ApiError::Internal("App state not configured".to_string()))?;
+            let pool = req
+                .app_data::<web::Data<DbPool>>()
+                .ok_or_else(|| ApiError::Internal("Database not configured".to_string()))?;
 
             // Decode JWT
-            let secret = env::var("JWT_SECRET").unwrap_or_else(|_| "default-secret".to_string());
             let token_data = decode::<Claims>(
                 token,
-                &DecodingKey::from_secret(secret.as_bytes()),
+                &DecodingKey::from_secret(state.config.jwt_secret.as_bytes()),
                 &Validation::new(Algorithm::HS256),
-            )
-            .map_err(|e| {
-                tracing::warn!("JWT decode error: {}", e);
-                actix_web::error::ErrorUnauthorized("Invalid token")
-            })?;
+            )?;
 
-            // Get database pool
-            let pool = req
-                .app_data::<web::Data<DbPool>>()
-                .ok_or_else(|| actix_web::error::ErrorInternalServerError("Database not configured"))?;
+            if token_data.claims.typ != ACCESS_TOKEN_TYPE {
+                return Err(ApiError::Unauthorized("Invalid token type".to_string()));
+            }
 
             // Look up user
             let user = db::users::get_by_open_id(pool.get_ref(), &token_data.claims.sub)
-                .await
-                .map_err(|e| {
-                    tracing::error!("User lookup error: {}", e);
-                    actix_web::error::ErrorInternalServerError("Database error")
-                })?
-                .ok_or_else(|| actix_web::error::ErrorUnauthorized("User not found"))?;
+                .await?
+                .ok_or_else(|| ApiError::Unauthorized("User not found".to_string()))?;
+
+            if token_data.claims.ver != user.token_version {
+                return Err(ApiError::Unauthorized("Token revoked".to_string()));
+            }
 
             Ok(AuthenticatedUser {
                 id: user.id,
                 open_id: user.open_id,
                 tier: user.tier,
                 query_count_today: user.query_count_today,
+                selected_county_id: user.selected_county_id,
             })
         })
     }
 }
 
-/// Check if user has exceeded their daily query limit
-pub fn check_query_limit(user: &AuthenticatedUser) -> Result<(), actix_web::Error> {
-    let limit = match user.tier.as_str() {
-        "pro" | "enterprise" => 1000,
-        _ => 5, // free tier
+/// Check (and record) a request against the user's tiered rate limits, then
+/// against the DB-backed daily quota on `users.queryCountToday`. The latter
+/// is a last-line guard against runaway LLM spend that still holds even if
+/// Redis (and so the `RateLimiter`) is unavailable: a Redis outage degrades
+/// to DB-only enforcement instead of failing the request outright, so the
+/// doc comment on `db::users::tier_daily_limit` actually holds in practice.
+pub async fn check_query_limit(
+    pool: &DbPool,
+    limiter: &RateLimiter,
+    user: &AuthenticatedUser,
+) -> Result<RateLimitStatus, ApiError> {
+    let redis_status = match limiter.check(&user.open_id, &user.tier).await {
+        Ok(status) => Some(status),
+        Err(RateLimitError::Redis(e)) => {
+            tracing::warn!("Rate limiter unavailable, degrading to DB-only quota enforcement: {}", e);
+            None
+        }
+        Err(e) => return Err(e.into()),
     };
 
-    if user.query_count_today >= limit {
-        return Err(actix_web::error::ErrorPaymentRequired(
-            "Daily query limit reached. Upgrade to Pro for unlimited queries."
-        ));
-    }
+    let today = chrono::Utc::now().date_naive().to_string();
+    let quota = db::users::check_and_consume_quota(pool, user.id, &today)
+        .await?
+        .ok_or_else(|| {
+            ApiError::Forbidden(
+                "Daily query limit reached. Upgrade to Pro for a higher daily limit.".to_string(),
+            )
+        })?;
 
-    Ok(())
+    Ok(redis_status.unwrap_or(RateLimitStatus {
+        limit: quota.limit as i64,
+        remaining: quota.remaining as i64,
+        reset_at: quota.reset_at,
+    }))
 }
 
-/// Generate a JWT token for a user
-pub fn generate_token(open_id: &str) -> Result<String, jsonwebtoken::errors::Error> {
+/// Generate a short-lived access token for a user
+pub fn generate_token(
+    config: &Config,
+    open_id: &str,
+    token_version: i32,
+) -> Result<String, jsonwebtoken::errors::Error> {
     use jsonwebtoken::{encode, EncodingKey, Header};
-    
-    let secret = env::var("JWT_SECRET").unwrap_or_else(|_| "default-secret".to_string());
+
     let now = chrono::Utc::now().timestamp() as usize;
-    
+
     let claims = Claims {
         sub: open_id.to_string(),
-        exp: now + 86400 * 30, // 30 days
+        typ: ACCESS_TOKEN_TYPE.to_string(),
+        ver: token_version,
+        exp: now + config.jwt_access_ttl as usize,
         iat: now,
     };
 
     encode(
         &Header::new(Algorithm::HS256),
         &claims,
-        &EncodingKey::from_secret(secret.as_bytes()),
+        &EncodingKey::from_secret(config.jwt_secret.as_bytes()),
     )
 }
+
+/// Generate a new opaque refresh token, returning both the raw token (to
+/// hand back to the client) and its hash (the only thing persisted).
+pub fn generate_refresh_token() -> (String, String) {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    let raw = hex::encode(bytes);
+    let hash = hash_refresh_token(&raw);
+    (raw, hash)
+}
+
+/// Hash a refresh token for storage/lookup. Refresh tokens are
+/// high-entropy opaque values (unlike passwords), so a fast SHA-256 digest
+/// is sufficient to keep a DB leak from yielding a usable token.
+pub fn hash_refresh_token(token: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(token.as_bytes());
+    hex::encode(hasher.finalize())
+}
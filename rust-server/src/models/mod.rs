@@ -3,9 +3,13 @@ pub mod county;
 pub mod protocol;
 pub mod query;
 pub mod feedback;
+pub mod refresh_token;
+pub mod pagination;
 
 pub use user::*;
 pub use county::*;
 pub use protocol::*;
 pub use query::*;
 pub use feedback::*;
+pub use refresh_token::*;
+pub use pagination::*;
@@ -0,0 +1,96 @@
+use serde::{Deserialize, Serialize};
+use sqids::Sqids;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::models::{County, ProtocolSearchResult, QueryHistoryItem};
+
+pub const DEFAULT_PAGE_LIMIT: i32 = 20;
+pub const MAX_PAGE_LIMIT: i32 = 100;
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct PageQuery {
+    pub limit: Option<i32>,
+    pub cursor: Option<String>,
+}
+
+impl PageQuery {
+    /// Clamp the requested page size and decode the opaque cursor, if any.
+    /// An invalid cursor is treated as "start from the beginning" rather
+    /// than an error, so a stale or tampered cursor just restarts paging.
+    pub fn resolve(&self) -> (i32, Option<Cursor>) {
+        let limit = self.limit.unwrap_or(DEFAULT_PAGE_LIMIT).clamp(1, MAX_PAGE_LIMIT);
+        let cursor = self.cursor.as_deref().and_then(Cursor::decode);
+        (limit, cursor)
+    }
+}
+
+/// Opaque keyset cursor over the last-seen `(sort_key, id)` pair. Encoded
+/// with `sqids` so clients never see raw offsets or row ids.
+///
+/// `sort_key` doesn't have to come from a column: a ranked endpoint (e.g.
+/// `db::protocols::search_page`) can derive it from a relevance score
+/// computed in Rust, as long as the full candidate set is scored and
+/// sorted once and pagination filters *that* list by `(sort_key, id)`
+/// rather than re-running the ranking per page. What doesn't work is
+/// falling back to an unrelated column (like `id DESC`) just because the
+/// real sort key isn't a SQL-orderable one — that silently drops the
+/// ranking the endpoint exists to provide.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cursor {
+    pub sort_key: i64,
+    pub id: i32,
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        Sqids::default()
+            .encode(&[zigzag_encode(self.sort_key), self.id as u64])
+            .unwrap_or_default()
+    }
+
+    pub fn decode(raw: &str) -> Option<Self> {
+        let parts = Sqids::default().decode(raw);
+        if parts.len() != 2 {
+            return None;
+        }
+        Some(Self {
+            sort_key: zigzag_decode(parts[0]),
+            id: parts[1] as i32,
+        })
+    }
+}
+
+fn zigzag_encode(n: i64) -> u64 {
+    ((n << 1) ^ (n >> 63)) as u64
+}
+
+fn zigzag_decode(n: u64) -> i64 {
+    ((n >> 1) as i64) ^ -((n & 1) as i64)
+}
+
+/// A page of results plus an opaque cursor for fetching the next one.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[aliases(CountyPage = Page<County>, SearchResultPage = Page<ProtocolSearchResult>, HistoryPage = Page<QueryHistoryItem>)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+    pub has_more: bool,
+}
+
+impl<T> Page<T> {
+    /// Build a page from `limit + 1` rows fetched in sort order: the extra
+    /// row, if present, signals more pages and is trimmed off.
+    pub fn from_rows(mut rows: Vec<T>, limit: i32, cursor_of: impl Fn(&T) -> Cursor) -> Self {
+        let has_more = rows.len() as i32 > limit;
+        if has_more {
+            rows.truncate(limit as usize);
+        }
+        let next_cursor = if has_more {
+            rows.last().map(|item| cursor_of(item).encode())
+        } else {
+            None
+        };
+
+        Self { items: rows, next_cursor, has_more }
+    }
+}
@@ -1,8 +1,9 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
+use utoipa::ToSchema;
 
-#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow, ToSchema)]
 pub struct ProtocolChunk {
     pub id: i32,
     #[sqlx(rename = "countyId")]
@@ -25,7 +26,7 @@ pub struct ProtocolChunk {
     pub created_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct ProtocolSearchResult {
     pub id: i32,
     pub county_id: i32,
@@ -68,7 +69,28 @@ pub struct SearchResponse {
     pub query: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A stored embedding vector for a protocol chunk, mirroring a
+/// `protocolChunkEmbeddings` row. Cosine ranking in `db::protocols` does not
+/// read this type; it joins the vector and norm onto the chunk row instead.
+/// Never exposed over the API, so it has no `ToSchema` impl.
+#[derive(Debug, Clone, FromRow)]
+pub struct ProtocolChunkEmbedding {
+    pub id: i32,
+    #[sqlx(rename = "protocolId")]
+    pub protocol_id: i32,
+    pub model: String,
+    pub dims: i32,
+    /// Little-endian `f32` bytes; decode with
+    /// `db::protocols::decode_vector`.
+    pub vector: Vec<u8>,
+    /// Precomputed L2 norm of `vector`, cached so cosine similarity doesn't
+    /// recompute it on every query.
+    pub norm: f64,
+    #[sqlx(rename = "createdAt")]
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct ProtocolStats {
     pub total_protocols: i64,
     pub total_counties: i64,
@@ -1,6 +1,7 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
+use utoipa::ToSchema;
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct QueryLog {
@@ -28,7 +29,7 @@ pub struct CreateQueryLog {
     pub protocol_refs: Option<Vec<String>>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow, ToSchema)]
 pub struct QueryHistoryItem {
     pub id: i32,
     pub query_text: String,
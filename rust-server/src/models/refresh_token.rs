@@ -0,0 +1,18 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct RefreshToken {
+    pub id: i32,
+    #[sqlx(rename = "userId")]
+    pub user_id: i32,
+    #[sqlx(rename = "tokenHash")]
+    pub token_hash: String,
+    #[sqlx(rename = "expiresAt")]
+    pub expires_at: DateTime<Utc>,
+    #[sqlx(rename = "revokedAt")]
+    pub revoked_at: Option<DateTime<Utc>>,
+    #[sqlx(rename = "createdAt")]
+    pub created_at: DateTime<Utc>,
+}
@@ -1,6 +1,7 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
+use utoipa::ToSchema;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
@@ -44,6 +45,9 @@ pub struct User {
     pub query_count_today: i32,
     #[sqlx(rename = "lastQueryDate")]
     pub last_query_date: Option<String>,
+    /// Bumped to invalidate every access token issued for this user at once.
+    #[sqlx(rename = "tokenVersion")]
+    pub token_version: i32,
     #[sqlx(rename = "selectedCountyId")]
     pub selected_county_id: Option<i32>,
     #[sqlx(rename = "stripeCustomerId")]
@@ -70,7 +74,7 @@ pub struct CreateUser {
     pub login_method: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct UserResponse {
     pub id: i32,
     pub name: Option<String>,
@@ -0,0 +1,67 @@
+use utoipa::{
+    openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+    Modify, OpenApi,
+};
+
+struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "bearer_auth",
+                SecurityScheme::Http(
+                    HttpBuilder::new()
+                        .scheme(HttpAuthScheme::Bearer)
+                        .bearer_format("JWT")
+                        .build(),
+                ),
+            );
+        }
+    }
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        crate::routes::health::health,
+        crate::routes::health::ready,
+        crate::routes::search::semantic_search,
+        crate::routes::search::stream_answer,
+        crate::routes::search::get_stats,
+        crate::routes::search::get_by_county,
+        crate::routes::counties::get_states,
+        crate::routes::counties::get_agencies_by_state,
+        crate::routes::counties::get_all,
+        crate::routes::counties::get_by_id,
+        crate::routes::users::get_me,
+        crate::routes::users::update_selected_county,
+        crate::routes::users::get_history,
+    ),
+    components(
+        schemas(
+            crate::routes::health::HealthResponse,
+            crate::routes::search::SearchQuery,
+            crate::routes::search::SemanticSearchResponse,
+            crate::routes::users::UpdateCountyRequest,
+            crate::models::UserResponse,
+            crate::models::StateWithCount,
+            crate::models::CountyWithProtocolCount,
+            crate::models::County,
+            crate::models::ProtocolChunk,
+            crate::models::ProtocolStats,
+            crate::models::ProtocolSearchResult,
+            crate::models::QueryHistoryItem,
+            crate::models::CountyPage,
+            crate::models::HistoryPage,
+        )
+    ),
+    modifiers(&SecurityAddon),
+    tags(
+        (name = "search", description = "Protocol search"),
+        (name = "counties", description = "Counties and agencies"),
+        (name = "users", description = "Authenticated user profile and history"),
+        (name = "health", description = "Service health"),
+    )
+)]
+pub struct ApiDoc;
@@ -0,0 +1,85 @@
+use actix_web::{web, HttpResponse};
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::config::AppState;
+use crate::db::{self, DbPool};
+use crate::error::ApiError;
+use crate::middleware::auth::{generate_refresh_token, generate_token, hash_refresh_token, AuthenticatedUser};
+
+#[derive(Debug, Deserialize)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RefreshResponse {
+    pub access_token: String,
+    pub refresh_token: String,
+}
+
+/// Validate and rotate a refresh token, returning a fresh access/refresh pair.
+pub async fn refresh(
+    pool: web::Data<DbPool>,
+    state: web::Data<AppState>,
+    body: web::Json<RefreshRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let token_hash = hash_refresh_token(&body.refresh_token);
+    let (raw_refresh_token, new_hash) = generate_refresh_token();
+    let expires_at = Utc::now() + Duration::days(state.config.jwt_refresh_ttl);
+
+    // `rotate` validates, revokes, and inserts the replacement in a single
+    // transaction with a row lock, so two requests racing on the same
+    // refresh token can't both succeed.
+    let user_id = db::refresh_tokens::rotate(pool.get_ref(), &token_hash, &new_hash, expires_at)
+        .await?
+        .ok_or_else(|| ApiError::Unauthorized("Invalid or expired refresh token".to_string()))?;
+
+    let user = db::users::get_by_id(pool.get_ref(), user_id)
+        .await?
+        .ok_or_else(|| ApiError::Unauthorized("User not found".to_string()))?;
+
+    let access_token = generate_token(&state.config, &user.open_id, user.token_version)?;
+
+    Ok(HttpResponse::Ok().json(RefreshResponse {
+        access_token,
+        refresh_token: raw_refresh_token,
+    }))
+}
+
+/// Revoke a refresh token. Idempotent: an already-revoked or unknown token
+/// is treated as a successful logout.
+pub async fn logout(
+    pool: web::Data<DbPool>,
+    body: web::Json<RefreshRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let token_hash = hash_refresh_token(&body.refresh_token);
+
+    if let Some(stored) = db::refresh_tokens::get_valid_by_hash(pool.get_ref(), &token_hash).await? {
+        db::refresh_tokens::revoke(pool.get_ref(), stored.id).await?;
+    }
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "success": true })))
+}
+
+/// Log out everywhere: revoke every outstanding refresh token for this user
+/// and bump `tokenVersion`, which invalidates the access token making this
+/// very request along with any others already issued.
+pub async fn logout_all(
+    pool: web::Data<DbPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, ApiError> {
+    db::refresh_tokens::revoke_all_for_user(pool.get_ref(), user.id).await?;
+    db::users::bump_token_version(pool.get_ref(), user.id).await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "success": true })))
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/api/auth")
+            .route("/refresh", web::post().to(refresh))
+            .route("/logout", web::post().to(logout))
+            .route("/logout-all", web::post().to(logout_all))
+    );
+}
@@ -1,72 +1,100 @@
-use actix_web::{web, HttpResponse, Result};
+use actix_web::{web, HttpResponse};
 use serde::Deserialize;
+use utoipa::IntoParams;
 
 use crate::db::{self, DbPool};
+use crate::error::ApiError;
+use crate::models::{County, CountyPage, CountyWithProtocolCount, Cursor, Page, PageQuery, StateWithCount};
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, IntoParams)]
 pub struct StateQuery {
     pub state: String,
 }
 
 /// Get all states with agency and protocol counts
-pub async fn get_states(pool: web::Data<DbPool>) -> Result<HttpResponse> {
-    let states = db::counties::get_states_with_counts(pool.get_ref())
-        .await
-        .map_err(|e| {
-            tracing::error!("States query error: {}", e);
-            actix_web::error::ErrorInternalServerError("Failed to get states")
-        })?;
+#[utoipa::path(
+    get,
+    path = "/api/counties/states",
+    tag = "counties",
+    responses(
+        (status = 200, description = "States with agency/protocol counts", body = [StateWithCount]),
+    )
+)]
+pub async fn get_states(pool: web::Data<DbPool>) -> Result<HttpResponse, ApiError> {
+    let states = db::counties::get_states_with_counts(pool.get_ref()).await?;
 
     Ok(HttpResponse::Ok().json(states))
 }
 
 /// Get agencies by state with protocol counts
+#[utoipa::path(
+    get,
+    path = "/api/counties/by-state",
+    tag = "counties",
+    params(StateQuery),
+    responses(
+        (status = 200, description = "Agencies in the given state", body = [CountyWithProtocolCount]),
+    )
+)]
 pub async fn get_agencies_by_state(
     pool: web::Data<DbPool>,
     query: web::Query<StateQuery>,
-) -> Result<HttpResponse> {
-    let agencies = db::counties::get_by_state(pool.get_ref(), &query.state)
-        .await
-        .map_err(|e| {
-            tracing::error!("Agencies query error: {}", e);
-            actix_web::error::ErrorInternalServerError("Failed to get agencies")
-        })?;
+) -> Result<HttpResponse, ApiError> {
+    let agencies = db::counties::get_by_state(pool.get_ref(), &query.state).await?;
 
     Ok(HttpResponse::Ok().json(agencies))
 }
 
-/// Get all counties/agencies
-pub async fn get_all(pool: web::Data<DbPool>) -> Result<HttpResponse> {
-    let counties = db::counties::get_all(pool.get_ref())
-        .await
-        .map_err(|e| {
-            tracing::error!("Counties query error: {}", e);
-            actix_web::error::ErrorInternalServerError("Failed to get counties")
-        })?;
+/// Get all counties/agencies, paginated with an opaque keyset cursor
+#[utoipa::path(
+    get,
+    path = "/api/counties",
+    tag = "counties",
+    params(PageQuery),
+    responses(
+        (status = 200, description = "A page of counties/agencies", body = CountyPage),
+    )
+)]
+pub async fn get_all(
+    pool: web::Data<DbPool>,
+    query: web::Query<PageQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let (limit, cursor) = query.resolve();
+
+    let ranked = db::counties::get_all_page(pool.get_ref(), limit, cursor).await?;
 
-    Ok(HttpResponse::Ok().json(counties))
+    let ranked_page = Page::from_rows(ranked, limit, |(c, rank)| Cursor { sort_key: *rank, id: c.id });
+    let page = Page {
+        items: ranked_page.items.into_iter().map(|(county, _)| county).collect(),
+        next_cursor: ranked_page.next_cursor,
+        has_more: ranked_page.has_more,
+    };
+
+    Ok(HttpResponse::Ok().json(page))
 }
 
 /// Get county by ID
+#[utoipa::path(
+    get,
+    path = "/api/counties/{id}",
+    tag = "counties",
+    params(("id" = i32, Path, description = "County id")),
+    responses(
+        (status = 200, description = "The county", body = County),
+        (status = 404, description = "County not found"),
+    )
+)]
 pub async fn get_by_id(
     pool: web::Data<DbPool>,
     path: web::Path<i32>,
-) -> Result<HttpResponse> {
+) -> Result<HttpResponse, ApiError> {
     let county_id = path.into_inner();
-    
+
     let county = db::counties::get_by_id(pool.get_ref(), county_id)
-        .await
-        .map_err(|e| {
-            tracing::error!("County query error: {}", e);
-            actix_web::error::ErrorInternalServerError("Failed to get county")
-        })?;
+        .await?
+        .ok_or_else(|| ApiError::NotFound("County not found".to_string()))?;
 
-    match county {
-        Some(c) => Ok(HttpResponse::Ok().json(c)),
-        None => Ok(HttpResponse::NotFound().json(serde_json::json!({
-            "error": "County not found"
-        }))),
-    }
+    Ok(HttpResponse::Ok().json(county))
 }
 
 pub fn configure(cfg: &mut web::ServiceConfig) {
@@ -1,9 +1,11 @@
-use actix_web::{web, HttpResponse, Result};
+use actix_web::{web, HttpResponse};
 use serde::Serialize;
+use utoipa::ToSchema;
 
 use crate::db::DbPool;
+use crate::error::ApiError;
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct HealthResponse {
     pub status: String,
     pub database: String,
@@ -11,7 +13,15 @@ pub struct HealthResponse {
 }
 
 /// Health check endpoint
-pub async fn health(pool: web::Data<DbPool>) -> Result<HttpResponse> {
+#[utoipa::path(
+    get,
+    path = "/health",
+    tag = "health",
+    responses(
+        (status = 200, description = "Service and database status", body = HealthResponse),
+    )
+)]
+pub async fn health(pool: web::Data<DbPool>) -> Result<HttpResponse, ApiError> {
     // Test database connection
     let db_status = match sqlx::query("SELECT 1")
         .execute(pool.get_ref())
@@ -29,7 +39,16 @@ pub async fn health(pool: web::Data<DbPool>) -> Result<HttpResponse> {
 }
 
 /// Ready check for load balancers
-pub async fn ready(pool: web::Data<DbPool>) -> Result<HttpResponse> {
+#[utoipa::path(
+    get,
+    path = "/ready",
+    tag = "health",
+    responses(
+        (status = 200, description = "Ready to serve traffic"),
+        (status = 503, description = "Database unavailable"),
+    )
+)]
+pub async fn ready(pool: web::Data<DbPool>) -> Result<HttpResponse, ApiError> {
     match sqlx::query("SELECT 1").execute(pool.get_ref()).await {
         Ok(_) => Ok(HttpResponse::Ok().body("ready")),
         Err(_) => Ok(HttpResponse::ServiceUnavailable().body("not ready")),
@@ -2,8 +2,10 @@ pub mod search;
 pub mod counties;
 pub mod users;
 pub mod health;
+pub mod auth;
 
 pub use search::*;
 pub use counties::*;
 pub use users::*;
 pub use health::*;
+pub use auth::*;
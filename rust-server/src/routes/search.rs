@@ -1,84 +1,209 @@
-use actix_web::{web, HttpResponse, Result};
+use actix_web::{web, HttpResponse};
+use async_stream::stream;
+use futures::StreamExt;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use utoipa::{IntoParams, ToSchema};
 
 use crate::db::{self, DbPool};
-use crate::models::{ProtocolSearchResult, SearchResponse, ProtocolStats};
+use crate::error::ApiError;
+use crate::middleware::auth::{check_query_limit, AuthenticatedUser};
+use crate::models::{CreateQueryLog, Cursor, Page, ProtocolChunk, ProtocolSearchResult, SearchResponse, ProtocolStats};
 use crate::services::llm::LlmClient;
+use crate::services::rate_limit::RateLimiter;
 
-#[derive(Debug, Deserialize)]
+/// Frame `data` as one or more SSE `data:` lines (splitting on embedded
+/// newlines, since an SSE data line can't itself contain one) followed by
+/// the blank line that terminates the event.
+fn sse_event(data: &str) -> web::Bytes {
+    let mut out = String::new();
+    for line in data.split('\n') {
+        out.push_str("data: ");
+        out.push_str(line);
+        out.push('\n');
+    }
+    out.push('\n');
+    web::Bytes::from(out)
+}
+
+#[derive(Debug, Deserialize, IntoParams, ToSchema)]
 pub struct SearchQuery {
     pub query: String,
     pub state: Option<String>,
     #[serde(rename = "countyId")]
     pub county_id: Option<i32>,
     pub limit: Option<i32>,
+    pub cursor: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 pub struct SemanticSearchResponse {
-    pub results: Vec<ProtocolSearchResult>,
+    #[serde(flatten)]
+    pub page: Page<ProtocolSearchResult>,
     pub answer: Option<String>,
-    pub total_count: i64,
 }
 
-/// Semantic search endpoint - searches protocols using keyword matching + LLM ranking
+/// Semantic search endpoint - ranks protocols by cosine similarity against
+/// stored embeddings (falling back to BM25 where none exist yet), then asks
+/// the LLM for a concise answer and a final relevance ranking
+#[utoipa::path(
+    get,
+    path = "/api/search",
+    tag = "search",
+    params(SearchQuery),
+    responses(
+        (status = 200, description = "Search results with an optional LLM-generated answer", body = SemanticSearchResponse),
+        (status = 401, description = "Missing or invalid access token"),
+        (status = 403, description = "Daily query limit reached"),
+        (status = 429, description = "Burst rate limit exceeded"),
+    ),
+    security(("bearer_auth" = []))
+)]
 pub async fn semantic_search(
     pool: web::Data<DbPool>,
     llm: web::Data<LlmClient>,
+    limiter: web::Data<RateLimiter>,
+    user: AuthenticatedUser,
     query: web::Query<SearchQuery>,
-) -> Result<HttpResponse> {
+) -> Result<HttpResponse, ApiError> {
+    let rate_status = check_query_limit(pool.get_ref(), &limiter, &user).await?;
     let limit = query.limit.unwrap_or(20).min(100);
-    
-    // Get raw search results from database
-    let protocols = db::protocols::search(
-        pool.get_ref(),
-        &query.query,
-        query.state.as_deref(),
-        query.county_id,
-        limit * 2, // Get more results for LLM to rank
-    )
-    .await
-    .map_err(|e| {
-        tracing::error!("Database search error: {}", e);
-        actix_web::error::ErrorInternalServerError("Search failed")
-    })?;
+    let cursor = query.cursor.as_deref().and_then(Cursor::decode);
+
+    // Embed the query once so results can be ranked by cosine similarity to
+    // stored protocol embeddings; a county that hasn't been backfilled yet
+    // (or an embedding call failure) falls back to the BM25-ranked
+    // `search_page`.
+    let query_vector = match llm.embed(&[query.query.clone()]).await {
+        Ok(mut vectors) => vectors.pop(),
+        Err(e) => {
+            tracing::warn!("Query embedding failed, falling back to BM25 search: {}", e);
+            None
+        }
+    };
+
+    let semantic_scored = match query_vector {
+        Some(vector) => {
+            db::protocols::semantic_search_page(
+                pool.get_ref(),
+                &vector,
+                query.state.as_deref(),
+                query.county_id,
+                limit,
+                cursor,
+            )
+            .await?
+        }
+        None => None,
+    };
+
+    let (protocols, cosine_scores): (Vec<(ProtocolChunk, f64)>, Option<HashMap<i32, f64>>) =
+        match semantic_scored {
+            Some(scored) => {
+                let cosine_scores = scored.iter().map(|(p, score)| (p.id, *score)).collect();
+                (scored, Some(cosine_scores))
+            }
+            None => {
+                let scored = db::protocols::search_page(
+                    pool.get_ref(),
+                    &query.query,
+                    query.state.as_deref(),
+                    query.county_id,
+                    limit,
+                    cursor,
+                )
+                .await?;
+                (scored, None)
+            }
+        };
 
     if protocols.is_empty() {
-        return Ok(HttpResponse::Ok().json(SemanticSearchResponse {
-            results: vec![],
-            answer: None,
-            total_count: 0,
-        }));
+        return Ok(HttpResponse::Ok()
+            .insert_header(("X-RateLimit-Limit", rate_status.limit.to_string()))
+            .insert_header(("X-RateLimit-Remaining", rate_status.remaining.to_string()))
+            .insert_header(("X-RateLimit-Reset", rate_status.reset_at.to_string()))
+            .json(SemanticSearchResponse {
+                page: Page { items: vec![], next_cursor: None, has_more: false },
+                answer: None,
+            }));
     }
 
-    // Get county info for each protocol
-    let mut results: Vec<ProtocolSearchResult> = Vec::new();
-    for protocol in protocols.iter().take(limit as usize) {
-        let county = db::counties::get_by_id(pool.get_ref(), protocol.county_id)
+    // Batch-fetch county metadata for the returned protocols in one query
+    // instead of looking each one up individually.
+    let county_ids: Vec<i32> = {
+        let mut ids: Vec<i32> = protocols.iter().map(|(p, _)| p.county_id).collect();
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    };
+    let counties_by_id: HashMap<i32, _> = db::counties::get_by_ids(pool.get_ref(), &county_ids)
+        .await?
+        .into_iter()
+        .map(|c| (c.id, c))
+        .collect();
+
+    let results: Vec<ProtocolSearchResult> = protocols
+        .iter()
+        .map(|(protocol, score)| {
+            let county = counties_by_id.get(&protocol.county_id);
+            ProtocolSearchResult {
+                id: protocol.id,
+                county_id: protocol.county_id,
+                county_name: county.map(|c| c.name.clone()).unwrap_or_default(),
+                state: county.map(|c| c.state.clone()).unwrap_or_default(),
+                protocol_number: protocol.protocol_number.clone(),
+                protocol_title: protocol.protocol_title.clone(),
+                section: protocol.section.clone(),
+                content: protocol.content.clone(),
+                source_pdf_url: protocol.source_pdf_url.clone(),
+                protocol_year: protocol.protocol_year,
+                last_verified_at: protocol.last_verified_at,
+                relevance_score: *score, // cosine or BM25 score; refined further below
+            }
+        })
+        .collect();
+
+    let mut page = Page::from_rows(results, limit, |r| Cursor {
+        sort_key: db::protocols::score_to_sort_key(r.relevance_score),
+        id: r.id,
+    });
+
+    // When we have cosine scores, blend them with a keyword score so both
+    // signals contribute instead of cosine alone deciding order. When we
+    // don't (BM25-only fallback), leave relevance_score as-is — keyword
+    // matching on top of BM25 would be a strictly weaker signal than BM25
+    // itself.
+    if cosine_scores.is_some() {
+        if let Err(e) = llm
+            .rank_results(&query.query, &mut page.items, cosine_scores.as_ref())
             .await
-            .ok()
-            .flatten();
-        
-        results.push(ProtocolSearchResult {
-            id: protocol.id,
-            county_id: protocol.county_id,
-            county_name: county.as_ref().map(|c| c.name.clone()).unwrap_or_default(),
-            state: county.as_ref().map(|c| c.state.clone()).unwrap_or_default(),
-            protocol_number: protocol.protocol_number.clone(),
-            protocol_title: protocol.protocol_title.clone(),
-            section: protocol.section.clone(),
-            content: protocol.content.clone(),
-            source_pdf_url: protocol.source_pdf_url.clone(),
-            protocol_year: protocol.protocol_year,
-            last_verified_at: protocol.last_verified_at,
-            relevance_score: 1.0, // Will be updated by LLM ranking
-        });
+        {
+            tracing::warn!("Failed to blend cosine/keyword ranking: {}", e);
+        }
     }
 
-    // Use LLM to generate a concise answer if we have results
-    let answer = if !results.is_empty() {
-        match llm.generate_answer(&query.query, &results).await {
-            Ok(ans) => Some(ans),
+    // Use the LLM to generate a concise answer and a relevance ranking for
+    // this page's results, re-sorting by the model's judgment.
+    let answer = if !page.items.is_empty() {
+        match llm.generate_answer(&query.query, &page.items).await {
+            Ok(generated) => {
+                let scores: HashMap<i32, f64> = generated
+                    .rankings
+                    .iter()
+                    .map(|r| (r.id, r.score))
+                    .collect();
+                for item in page.items.iter_mut() {
+                    if let Some(&score) = scores.get(&item.id) {
+                        item.relevance_score = score;
+                    }
+                }
+                page.items.sort_by(|a, b| {
+                    b.relevance_score
+                        .partial_cmp(&a.relevance_score)
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                });
+                Some(generated.answer)
+            }
             Err(e) => {
                 tracing::warn!("LLM answer generation failed: {}", e);
                 None
@@ -88,39 +213,162 @@ pub async fn semantic_search(
         None
     };
 
-    let total = results.len() as i64;
-    Ok(HttpResponse::Ok().json(SemanticSearchResponse {
-        results,
-        answer,
-        total_count: total,
-    }))
+    let protocol_refs: Vec<String> = page.items.iter().map(|r| r.id.to_string()).collect();
+    let log_county_id = query
+        .county_id
+        .or(user.selected_county_id)
+        .or_else(|| page.items.first().map(|r| r.county_id));
+
+    if let Some(county_id) = log_county_id {
+        let log = CreateQueryLog {
+            user_id: user.id,
+            county_id,
+            query_text: query.query.clone(),
+            response_text: answer.clone(),
+            protocol_refs: if protocol_refs.is_empty() { None } else { Some(protocol_refs) },
+        };
+        if let Err(e) = db::query_logs::create(pool.get_ref(), &log).await {
+            tracing::warn!("Failed to persist query log: {}", e);
+        }
+    }
+
+    Ok(HttpResponse::Ok()
+        .insert_header(("X-RateLimit-Limit", rate_status.limit.to_string()))
+        .insert_header(("X-RateLimit-Remaining", rate_status.remaining.to_string()))
+        .insert_header(("X-RateLimit-Reset", rate_status.reset_at.to_string()))
+        .json(SemanticSearchResponse { page, answer }))
+}
+
+/// Stream an LLM-generated answer over Server-Sent Events as it's
+/// generated, for clients that want to render guidance progressively
+/// instead of waiting on the full `/api/search` response. Runs only the
+/// BM25-ranked candidate lookup that `/api/search` falls back to (no cosine
+/// ranking against stored embeddings), and skips pagination and the
+/// JSON-ranking pass, since only the prose answer is streamed here.
+#[utoipa::path(
+    get,
+    path = "/api/search/stream",
+    tag = "search",
+    params(SearchQuery),
+    responses(
+        (status = 200, description = "`text/event-stream` of answer tokens, terminated by a `data: [DONE]` event"),
+        (status = 401, description = "Missing or invalid access token"),
+        (status = 403, description = "Daily query limit reached"),
+        (status = 429, description = "Burst rate limit exceeded"),
+    ),
+    security(("bearer_auth" = []))
+)]
+pub async fn stream_answer(
+    pool: web::Data<DbPool>,
+    llm: web::Data<LlmClient>,
+    limiter: web::Data<RateLimiter>,
+    user: AuthenticatedUser,
+    query: web::Query<SearchQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let rate_status = check_query_limit(pool.get_ref(), &limiter, &user).await?;
+    let limit = query.limit.unwrap_or(20).min(100);
+
+    let protocols = db::protocols::search(
+        pool.get_ref(),
+        &query.query,
+        query.state.as_deref(),
+        query.county_id,
+        limit,
+    )
+    .await?;
+
+    let county_ids: Vec<i32> = {
+        let mut ids: Vec<i32> = protocols.iter().map(|(p, _)| p.county_id).collect();
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    };
+    let counties_by_id: HashMap<i32, _> = db::counties::get_by_ids(pool.get_ref(), &county_ids)
+        .await?
+        .into_iter()
+        .map(|c| (c.id, c))
+        .collect();
+
+    let results: Vec<ProtocolSearchResult> = protocols
+        .iter()
+        .map(|(protocol, score)| {
+            let county = counties_by_id.get(&protocol.county_id);
+            ProtocolSearchResult {
+                id: protocol.id,
+                county_id: protocol.county_id,
+                county_name: county.map(|c| c.name.clone()).unwrap_or_default(),
+                state: county.map(|c| c.state.clone()).unwrap_or_default(),
+                protocol_number: protocol.protocol_number.clone(),
+                protocol_title: protocol.protocol_title.clone(),
+                section: protocol.section.clone(),
+                content: protocol.content.clone(),
+                source_pdf_url: protocol.source_pdf_url.clone(),
+                protocol_year: protocol.protocol_year,
+                last_verified_at: protocol.last_verified_at,
+                relevance_score: *score,
+            }
+        })
+        .collect();
+
+    let query_text = query.query.clone();
+    let llm = llm.into_inner();
+
+    let body = stream! {
+        let answer_stream = llm.generate_answer_stream(&query_text, &results);
+        futures::pin_mut!(answer_stream);
+        while let Some(token) = answer_stream.next().await {
+            match token {
+                Ok(text) => yield Ok::<_, actix_web::Error>(sse_event(&text)),
+                Err(e) => {
+                    tracing::warn!("Answer stream failed: {}", e);
+                    break;
+                }
+            }
+        }
+        yield Ok::<_, actix_web::Error>(sse_event("[DONE]"));
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .insert_header(("X-RateLimit-Limit", rate_status.limit.to_string()))
+        .insert_header(("X-RateLimit-Remaining", rate_status.remaining.to_string()))
+        .insert_header(("X-RateLimit-Reset", rate_status.reset_at.to_string()))
+        .streaming(body))
 }
 
 /// Get protocol statistics
-pub async fn get_stats(pool: web::Data<DbPool>) -> Result<HttpResponse> {
-    let stats = db::protocols::get_stats(pool.get_ref())
-        .await
-        .map_err(|e| {
-            tracing::error!("Stats query error: {}", e);
-            actix_web::error::ErrorInternalServerError("Failed to get stats")
-        })?;
+#[utoipa::path(
+    get,
+    path = "/api/search/stats",
+    tag = "search",
+    responses(
+        (status = 200, description = "Aggregate protocol/county counts", body = ProtocolStats),
+    )
+)]
+pub async fn get_stats(pool: web::Data<DbPool>) -> Result<HttpResponse, ApiError> {
+    let stats = db::protocols::get_stats(pool.get_ref()).await?;
 
     Ok(HttpResponse::Ok().json(stats))
 }
 
 /// Get protocols by county ID
+#[utoipa::path(
+    get,
+    path = "/api/search/county/{id}",
+    tag = "search",
+    params(("id" = i32, Path, description = "County id")),
+    responses(
+        (status = 200, description = "Protocols for the given county", body = [ProtocolChunk]),
+    )
+)]
 pub async fn get_by_county(
     pool: web::Data<DbPool>,
     path: web::Path<i32>,
-) -> Result<HttpResponse> {
+) -> Result<HttpResponse, ApiError> {
     let county_id = path.into_inner();
-    
-    let protocols = db::protocols::get_by_county(pool.get_ref(), county_id)
-        .await
-        .map_err(|e| {
-            tracing::error!("Protocol query error: {}", e);
-            actix_web::error::ErrorInternalServerError("Failed to get protocols")
-        })?;
+
+    let protocols = db::protocols::get_by_county(pool.get_ref(), county_id).await?;
 
     Ok(HttpResponse::Ok().json(protocols))
 }
@@ -129,6 +377,7 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
     cfg.service(
         web::scope("/api/search")
             .route("", web::get().to(semantic_search))
+            .route("/stream", web::get().to(stream_answer))
             .route("/stats", web::get().to(get_stats))
             .route("/county/{id}", web::get().to(get_by_county))
     );
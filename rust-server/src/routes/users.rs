@@ -1,67 +1,92 @@
-use actix_web::{web, HttpResponse, Result};
+use actix_web::{web, HttpResponse};
 use serde::Deserialize;
+use utoipa::ToSchema;
 
 use crate::db::{self, DbPool};
+use crate::error::ApiError;
 use crate::middleware::auth::AuthenticatedUser;
-use crate::models::UserResponse;
+use crate::models::{Cursor, HistoryPage, Page, PageQuery, UserResponse};
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct UpdateCountyRequest {
     #[serde(rename = "countyId")]
     pub county_id: i32,
 }
 
 /// Get current user profile
+#[utoipa::path(
+    get,
+    path = "/api/users/me",
+    tag = "users",
+    responses(
+        (status = 200, description = "The authenticated user's profile", body = UserResponse),
+        (status = 401, description = "Missing or invalid access token"),
+        (status = 404, description = "User not found"),
+    ),
+    security(("bearer_auth" = []))
+)]
 pub async fn get_me(
     pool: web::Data<DbPool>,
     user: AuthenticatedUser,
-) -> Result<HttpResponse> {
+) -> Result<HttpResponse, ApiError> {
     let db_user = db::users::get_by_id(pool.get_ref(), user.id)
-        .await
-        .map_err(|e| {
-            tracing::error!("User query error: {}", e);
-            actix_web::error::ErrorInternalServerError("Failed to get user")
-        })?;
+        .await?
+        .ok_or_else(|| ApiError::NotFound("User not found".to_string()))?;
 
-    match db_user {
-        Some(u) => Ok(HttpResponse::Ok().json(UserResponse::from(u))),
-        None => Ok(HttpResponse::NotFound().json(serde_json::json!({
-            "error": "User not found"
-        }))),
-    }
+    Ok(HttpResponse::Ok().json(UserResponse::from(db_user)))
 }
 
 /// Update user's selected county
+#[utoipa::path(
+    put,
+    path = "/api/users/county",
+    tag = "users",
+    request_body = UpdateCountyRequest,
+    responses(
+        (status = 200, description = "County updated"),
+        (status = 401, description = "Missing or invalid access token"),
+    ),
+    security(("bearer_auth" = []))
+)]
 pub async fn update_selected_county(
     pool: web::Data<DbPool>,
     user: AuthenticatedUser,
     body: web::Json<UpdateCountyRequest>,
-) -> Result<HttpResponse> {
-    db::users::update_selected_county(pool.get_ref(), user.id, body.county_id)
-        .await
-        .map_err(|e| {
-            tracing::error!("Update county error: {}", e);
-            actix_web::error::ErrorInternalServerError("Failed to update county")
-        })?;
+) -> Result<HttpResponse, ApiError> {
+    db::users::update_selected_county(pool.get_ref(), user.id, body.county_id).await?;
 
     Ok(HttpResponse::Ok().json(serde_json::json!({
         "success": true
     })))
 }
 
-/// Get user's query history
+/// Get user's query history, paginated with an opaque keyset cursor
+#[utoipa::path(
+    get,
+    path = "/api/users/history",
+    tag = "users",
+    params(PageQuery),
+    responses(
+        (status = 200, description = "A page of the user's query history", body = HistoryPage),
+        (status = 401, description = "Missing or invalid access token"),
+    ),
+    security(("bearer_auth" = []))
+)]
 pub async fn get_history(
     pool: web::Data<DbPool>,
     user: AuthenticatedUser,
-) -> Result<HttpResponse> {
-    let history = db::query_logs::get_user_history(pool.get_ref(), user.id, 50)
-        .await
-        .map_err(|e| {
-            tracing::error!("History query error: {}", e);
-            actix_web::error::ErrorInternalServerError("Failed to get history")
-        })?;
+    query: web::Query<PageQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let (limit, cursor) = query.resolve();
 
-    Ok(HttpResponse::Ok().json(history))
+    let history = db::query_logs::get_user_history_page(pool.get_ref(), user.id, limit, cursor).await?;
+
+    let page = Page::from_rows(history, limit, |item| Cursor {
+        sort_key: item.created_at.timestamp(),
+        id: item.id,
+    });
+
+    Ok(HttpResponse::Ok().json(page))
 }
 
 pub fn configure(cfg: &mut web::ServiceConfig) {
@@ -1,8 +1,11 @@
+use async_stream::try_stream;
+use futures::{Stream, StreamExt};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use std::env;
+use std::collections::HashMap;
 use thiserror::Error;
 
+use crate::config::Config;
 use crate::models::ProtocolSearchResult;
 
 #[derive(Error, Debug)]
@@ -11,8 +14,8 @@ pub enum LlmError {
     RequestError(#[from] reqwest::Error),
     #[error("API error: {0}")]
     ApiError(String),
-    #[error("Missing API key")]
-    MissingApiKey,
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
 }
 
 #[derive(Debug, Clone)]
@@ -20,6 +23,9 @@ pub struct LlmClient {
     client: Client,
     api_key: String,
     base_url: String,
+    model: String,
+    max_tokens: u32,
+    temperature: f32,
 }
 
 #[derive(Debug, Serialize)]
@@ -34,6 +40,7 @@ struct ChatRequest {
     messages: Vec<ChatMessage>,
     max_tokens: u32,
     temperature: f32,
+    stream: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -51,35 +58,94 @@ struct ChatMessageResponse {
     content: String,
 }
 
-impl LlmClient {
-    pub fn new() -> Result<Self, LlmError> {
-        let api_key = env::var("OPENAI_API_KEY")
-            .or_else(|_| env::var("LLM_API_KEY"))
-            .map_err(|_| LlmError::MissingApiKey)?;
+/// One `data: {...}` chunk of an SSE chat-completions stream.
+#[derive(Debug, Deserialize)]
+struct ChatStreamChunk {
+    choices: Vec<ChatStreamChoice>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatStreamChoice {
+    delta: ChatStreamDelta,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatStreamDelta {
+    #[serde(default)]
+    content: Option<String>,
+}
+
+/// Model used by `LlmClient::embed`. Stored alongside each vector in
+/// `protocolChunkEmbeddings` so a future model change doesn't silently mix
+/// incompatible embeddings together.
+pub const EMBEDDING_MODEL: &str = "text-embedding-3-small";
 
-        let base_url = env::var("LLM_BASE_URL")
-            .unwrap_or_else(|_| "https://api.openai.com/v1".to_string());
+#[derive(Debug, Serialize)]
+struct EmbeddingsRequest<'a> {
+    model: &'a str,
+    input: &'a [String],
+}
 
-        Ok(Self {
+#[derive(Debug, Deserialize)]
+struct EmbeddingsResponse {
+    data: Vec<EmbeddingData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingData {
+    embedding: Vec<f32>,
+}
+
+const STREAM_SYSTEM_PROMPT: &str = r#"You are an EMS protocol assistant. Provide concise, actionable answers based on the provided protocol excerpts.
+Focus on:
+- Key steps and interventions
+- Medication dosages when mentioned
+- Critical decision points
+Keep responses brief and field-ready. Always cite the protocol number."#;
+
+/// A single result's LLM-judged relevance, keyed by `ProtocolSearchResult::id`.
+#[derive(Debug, Deserialize, Clone, Copy)]
+pub struct ResultRanking {
+    pub id: i32,
+    pub score: f64,
+}
+
+/// The answer text plus a relevance ranking for each result that was in
+/// context, as judged by the model rather than hardcoded.
+#[derive(Debug, Deserialize)]
+pub struct GeneratedAnswer {
+    pub answer: String,
+    #[serde(default)]
+    pub rankings: Vec<ResultRanking>,
+}
+
+impl LlmClient {
+    pub fn new(config: &Config) -> Self {
+        Self {
             client: Client::new(),
-            api_key,
-            base_url,
-        })
+            api_key: config.llm_api_key.clone(),
+            base_url: config.llm_base_url.clone(),
+            model: config.llm_model.clone(),
+            max_tokens: config.llm_max_tokens,
+            temperature: config.llm_temperature,
+        }
     }
 
-    /// Generate a concise answer from protocol search results
+    /// Generate a concise answer from protocol search results, along with a
+    /// per-result relevance ranking judged by the model.
     pub async fn generate_answer(
         &self,
         query: &str,
         results: &[ProtocolSearchResult],
-    ) -> Result<String, LlmError> {
+    ) -> Result<GeneratedAnswer, LlmError> {
         // Build context from search results
         let context = results
             .iter()
             .take(5)
             .map(|r| {
                 format!(
-                    "Protocol: {} - {}\nAgency: {} ({})\nContent: {}\n",
+                    "ID: {}\nProtocol: {} - {}\nAgency: {} ({})\nContent: {}\n",
+                    r.id,
                     r.protocol_number,
                     r.protocol_title,
                     r.county_name,
@@ -90,20 +156,24 @@ impl LlmClient {
             .collect::<Vec<_>>()
             .join("\n---\n");
 
-        let system_prompt = r#"You are an EMS protocol assistant. Provide concise, actionable answers based on the provided protocol excerpts. 
+        let system_prompt = r#"You are an EMS protocol assistant. Provide concise, actionable answers based on the provided protocol excerpts.
 Focus on:
 - Key steps and interventions
 - Medication dosages when mentioned
 - Critical decision points
-Keep responses brief and field-ready. Always cite the protocol number."#;
+Keep responses brief and field-ready. Always cite the protocol number.
+
+Respond with ONLY a JSON object of the form:
+{"answer": "<your answer>", "rankings": [{"id": <protocol id>, "score": <0.0-1.0 relevance>}, ...]}
+Include one ranking entry per protocol ID given, ordered most to least relevant."#;
 
         let user_prompt = format!(
-            "Question: {}\n\nRelevant Protocols:\n{}\n\nProvide a concise answer based on these protocols.",
+            "Question: {}\n\nRelevant Protocols:\n{}\n\nRespond with the JSON object described above.",
             query, context
         );
 
         let request = ChatRequest {
-            model: "gpt-4o-mini".to_string(),
+            model: self.model.clone(),
             messages: vec![
                 ChatMessage {
                     role: "system".to_string(),
@@ -114,8 +184,9 @@ Keep responses brief and field-ready. Always cite the protocol number."#;
                     content: user_prompt,
                 },
             ],
-            max_tokens: 500,
-            temperature: 0.3,
+            max_tokens: self.max_tokens,
+            temperature: self.temperature,
+            stream: false,
         };
 
         let response = self
@@ -133,19 +204,147 @@ Keep responses brief and field-ready. Always cite the protocol number."#;
         }
 
         let chat_response: ChatResponse = response.json().await?;
-        
-        chat_response
+
+        let content = chat_response
             .choices
             .first()
             .map(|c| c.message.content.clone())
-            .ok_or_else(|| LlmError::ApiError("No response from LLM".to_string()))
+            .ok_or_else(|| LlmError::ApiError("No response from LLM".to_string()))?;
+
+        serde_json::from_str(&content)
+            .map_err(|e| LlmError::ApiError(format!("Malformed LLM response: {}", e)))
     }
 
-    /// Rank search results by relevance using LLM
+    /// Stream an answer token-by-token as the chat-completions endpoint
+    /// generates it, for callers that want to render guidance progressively
+    /// (e.g. over SSE) instead of waiting on the full response. Unlike
+    /// `generate_answer`, this does not ask for a JSON-wrapped ranking — it's
+    /// plain prose, since a structured envelope can't be rendered
+    /// incrementally.
+    pub fn generate_answer_stream<'a>(
+        &'a self,
+        query: &str,
+        results: &[ProtocolSearchResult],
+    ) -> impl Stream<Item = Result<String, LlmError>> + 'a {
+        let context = results
+            .iter()
+            .take(5)
+            .map(|r| {
+                format!(
+                    "Protocol: {} - {}\nAgency: {} ({})\nContent: {}\n",
+                    r.protocol_number,
+                    r.protocol_title,
+                    r.county_name,
+                    r.state,
+                    &r.content[..r.content.len().min(500)]
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n---\n");
+
+        let user_prompt = format!(
+            "Question: {}\n\nRelevant Protocols:\n{}\n\nProvide a concise answer based on these protocols.",
+            query, context
+        );
+
+        try_stream! {
+            let request = ChatRequest {
+                model: self.model.clone(),
+                messages: vec![
+                    ChatMessage {
+                        role: "system".to_string(),
+                        content: STREAM_SYSTEM_PROMPT.to_string(),
+                    },
+                    ChatMessage {
+                        role: "user".to_string(),
+                        content: user_prompt,
+                    },
+                ],
+                max_tokens: self.max_tokens,
+                temperature: self.temperature,
+                stream: true,
+            };
+
+            let response = self
+                .client
+                .post(format!("{}/chat/completions", self.base_url))
+                .header("Authorization", format!("Bearer {}", self.api_key))
+                .header("Content-Type", "application/json")
+                .json(&request)
+                .send()
+                .await?;
+
+            if !response.status().is_success() {
+                let error_text = response.text().await.unwrap_or_default();
+                Err(LlmError::ApiError(error_text))?;
+            }
+
+            let mut bytes = response.bytes_stream();
+            let mut buffer = String::new();
+
+            while let Some(chunk) = bytes.next().await {
+                buffer.push_str(&String::from_utf8_lossy(&chunk?));
+
+                while let Some(newline_pos) = buffer.find('\n') {
+                    let line = buffer[..newline_pos].trim_end_matches('\r').to_string();
+                    buffer.drain(..=newline_pos);
+
+                    let Some(data) = line.strip_prefix("data: ") else {
+                        continue;
+                    };
+                    if data == "[DONE]" {
+                        return;
+                    }
+
+                    let delta: ChatStreamChunk = serde_json::from_str(data)
+                        .map_err(|e| LlmError::ApiError(format!("Malformed stream chunk: {}", e)))?;
+
+                    if let Some(content) = delta.choices.into_iter().next().and_then(|c| c.delta.content) {
+                        if !content.is_empty() {
+                            yield content;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Embed a batch of texts in a single request, returning one vector per
+    /// input in the same order. Used both to backfill
+    /// `protocolChunkEmbeddings` and to embed a search query for
+    /// `db::protocols::semantic_search_page`.
+    pub async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, LlmError> {
+        let request = EmbeddingsRequest {
+            model: EMBEDDING_MODEL,
+            input: texts,
+        };
+
+        let response = self
+            .client
+            .post(format!("{}/embeddings", self.base_url))
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .header("Content-Type", "application/json")
+            .json(&request)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(LlmError::ApiError(error_text));
+        }
+
+        let parsed: EmbeddingsResponse = response.json().await?;
+        Ok(parsed.data.into_iter().map(|d| d.embedding).collect())
+    }
+
+    /// Rank search results by relevance using keyword matching, optionally
+    /// blended with a precomputed cosine similarity score (e.g. from
+    /// `db::protocols::semantic_search_page`) keyed by `ProtocolSearchResult::id`.
     pub async fn rank_results(
         &self,
         query: &str,
         results: &mut [ProtocolSearchResult],
+        cosine_scores: Option<&HashMap<i32, f64>>,
     ) -> Result<(), LlmError> {
         // For now, use simple keyword matching for ranking
         // In production, this could use embeddings or LLM-based ranking
@@ -155,9 +354,9 @@ Keep responses brief and field-ready. Always cite the protocol number."#;
         for result in results.iter_mut() {
             let title_lower = result.protocol_title.to_lowercase();
             let content_lower = result.content.to_lowercase();
-            
+
             let mut score = 0.0;
-            
+
             for keyword in &keywords {
                 // Title matches are worth more
                 if title_lower.contains(keyword) {
@@ -168,9 +367,14 @@ Keep responses brief and field-ready. Always cite the protocol number."#;
                     score += 1.0;
                 }
             }
-            
+
             // Normalize score
-            result.relevance_score = score / (keywords.len() as f64 * 3.0);
+            let keyword_score = score / (keywords.len() as f64 * 3.0);
+
+            result.relevance_score = match cosine_scores.and_then(|scores| scores.get(&result.id)) {
+                Some(cosine) => 0.4 * keyword_score + 0.6 * cosine,
+                None => keyword_score,
+            };
         }
 
         // Sort by relevance score descending
@@ -183,9 +387,3 @@ Keep responses brief and field-ready. Always cite the protocol number."#;
         Ok(())
     }
 }
-
-impl Default for LlmClient {
-    fn default() -> Self {
-        Self::new().expect("Failed to create LLM client")
-    }
-}
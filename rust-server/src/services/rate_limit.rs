@@ -0,0 +1,195 @@
+use dashmap::DashMap;
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+
+use crate::config::Config;
+
+const DAY_SECS: i64 = 86_400;
+const BURST_WINDOW_SECS: i64 = 60;
+const FLUSH_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Error, Debug)]
+pub enum RateLimitError {
+    #[error("redis error: {0}")]
+    Redis(#[from] redis::RedisError),
+    #[error("daily query limit reached")]
+    DailyLimitExceeded,
+    #[error("too many requests, slow down")]
+    BurstLimitExceeded,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitStatus {
+    pub limit: i64,
+    pub remaining: i64,
+    pub reset_at: i64,
+}
+
+/// Per-process cache of a single rate-limit window, updated optimistically
+/// between flushes to Redis.
+struct LocalWindow {
+    /// Increments applied locally since the last flush, not yet sent to Redis.
+    pending: AtomicU64,
+    /// Remaining quota as of the last reconciliation with Redis, minus any
+    /// pending local increments.
+    remaining: AtomicI64,
+    /// The tier limit this window was seeded with.
+    limit: i64,
+}
+
+fn tier_daily_limit(tier: &str) -> i64 {
+    match tier {
+        "pro" | "enterprise" => 1000,
+        _ => 5,
+    }
+}
+
+fn tier_burst_limit(tier: &str) -> i64 {
+    match tier {
+        "pro" | "enterprise" => 60,
+        _ => 3,
+    }
+}
+
+/// Redis-backed fixed-window rate limiter with an in-process deferred layer
+/// so hot paths don't pay a Redis round-trip on every request.
+#[derive(Clone)]
+pub struct RateLimiter {
+    conn: ConnectionManager,
+    windows: Arc<DashMap<String, LocalWindow>>,
+}
+
+impl RateLimiter {
+    pub async fn new(config: &Config) -> Result<Self, RateLimitError> {
+        let client = redis::Client::open(config.redis_url.clone())?;
+        let conn = ConnectionManager::new(client).await?;
+        let windows = Arc::new(DashMap::new());
+
+        let limiter = Self { conn, windows };
+        limiter.spawn_flush_task();
+        Ok(limiter)
+    }
+
+    fn spawn_flush_task(&self) {
+        let conn = self.conn.clone();
+        let windows = self.windows.clone();
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(FLUSH_INTERVAL);
+            loop {
+                interval.tick().await;
+                if let Err(e) = Self::flush_pending(&conn, &windows).await {
+                    tracing::warn!("rate limiter flush failed: {}", e);
+                }
+            }
+        });
+    }
+
+    async fn flush_pending(
+        conn: &ConnectionManager,
+        windows: &DashMap<String, LocalWindow>,
+    ) -> Result<(), RateLimitError> {
+        let mut conn = conn.clone();
+
+        for entry in windows.iter() {
+            let key = entry.key().clone();
+            let delta = entry.value().pending.swap(0, Ordering::SeqCst);
+            if delta == 0 {
+                continue;
+            }
+
+            let window_secs = if key.starts_with("rl:burst:") {
+                BURST_WINDOW_SECS
+            } else {
+                DAY_SECS
+            };
+
+            let (count,): (i64,) = redis::pipe()
+                .atomic()
+                .cmd("INCRBY")
+                .arg(&key)
+                .arg(delta)
+                .cmd("EXPIRE")
+                .arg(&key)
+                .arg(window_secs)
+                .ignore()
+                .query_async(&mut conn)
+                .await?;
+
+            // Reconcile: the authoritative count may differ from our optimistic
+            // guess if another process touched the same key. Increments made
+            // locally since the swap above are still pending and stay deducted.
+            let window = entry.value();
+            let still_pending = window.pending.load(Ordering::SeqCst) as i64;
+            window
+                .remaining
+                .store(window.limit - count - still_pending, Ordering::SeqCst);
+        }
+
+        Ok(())
+    }
+
+    async fn window_count(
+        conn: &ConnectionManager,
+        key: &str,
+    ) -> Result<i64, RateLimitError> {
+        let mut conn = conn.clone();
+        let count: Option<i64> = conn.get(key).await?;
+        Ok(count.unwrap_or(0))
+    }
+
+    /// Check (and record) a single request against both the tier's daily
+    /// quota and its short-term burst cap. Returns the post-increment status
+    /// for the daily window so handlers can surface `X-RateLimit-*` headers.
+    pub async fn check(&self, open_id: &str, tier: &str) -> Result<RateLimitStatus, RateLimitError> {
+        let daily_limit = tier_daily_limit(tier);
+        let burst_limit = tier_burst_limit(tier);
+        let now = chrono::Utc::now().timestamp();
+
+        let day_window = now / DAY_SECS;
+        let daily_key = format!("rl:{}:{}", open_id, day_window);
+        let reset_at = (day_window + 1) * DAY_SECS;
+
+        let burst_window = now / BURST_WINDOW_SECS;
+        let burst_key = format!("rl:burst:{}:{}", open_id, burst_window);
+
+        let burst_count = self.record_increment(&burst_key, burst_limit).await?;
+        if burst_count > burst_limit {
+            return Err(RateLimitError::BurstLimitExceeded);
+        }
+
+        let daily_count = self.record_increment(&daily_key, daily_limit).await?;
+        if daily_count > daily_limit {
+            return Err(RateLimitError::DailyLimitExceeded);
+        }
+
+        Ok(RateLimitStatus {
+            limit: daily_limit,
+            remaining: (daily_limit - daily_count).max(0),
+            reset_at,
+        })
+    }
+
+    /// Apply a local increment against the cached remaining quota, seeding
+    /// the cache from Redis on first use for a given key.
+    async fn record_increment(&self, key: &str, limit: i64) -> Result<i64, RateLimitError> {
+        if !self.windows.contains_key(key) {
+            let count = Self::window_count(&self.conn, key).await?;
+            self.windows.entry(key.to_string()).or_insert_with(|| LocalWindow {
+                pending: AtomicU64::new(0),
+                remaining: AtomicI64::new(limit - count),
+                limit,
+            });
+        }
+
+        let window = self.windows.get(key).expect("window entry just inserted");
+        window.pending.fetch_add(1, Ordering::SeqCst);
+        let remaining_after = window.remaining.fetch_sub(1, Ordering::SeqCst) - 1;
+
+        Ok(limit - remaining_after)
+    }
+}